@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::storage::SeriesKey;
+use crate::types::{Alert, Severity};
+
+/// Identifies one alerting condition: a series as seen by one analyzer
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActiveKey {
+    pub series: SeriesKey,
+    pub source: String,
+}
+
+impl ActiveKey {
+    pub fn of(alert: &Alert) -> Self {
+        Self {
+            series: SeriesKey::new(alert.metric, &alert.labels),
+            source: alert.source.clone(),
+        }
+    }
+}
+
+/// State of one firing condition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveAlert {
+    /// Most recent firing alert, with escalation applied
+    pub alert: Alert,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Times the condition was raised since it became active
+    pub occurrences: u32,
+    /// When the condition first reached Critical, natively or by escalation
+    pub critical_since: Option<DateTime<Utc>>,
+    /// Whether any firing notification went out; only those get a RESOLVED
+    pub notified: bool,
+    /// Whether the incident was acknowledged because a silence took it over
+    #[serde(default)]
+    pub acknowledged: bool,
+}
+
+/// Tracks firing conditions. Analyzers re-raise a condition on every pass
+/// while it holds, so a condition is clear once it has not been seen for
+/// `clear_secs`.
+pub struct ActiveAlerts {
+    clear_secs: i64,
+    active: HashMap<ActiveKey, ActiveAlert>,
+}
+
+impl ActiveAlerts {
+    pub fn new(clear_secs: u64) -> Self {
+        Self {
+            clear_secs: clear_secs as i64,
+            active: HashMap::new(),
+        }
+    }
+
+    /// Record an occurrence and stamp the alert with the condition's start time
+    pub fn observe(&mut self, alert: &mut Alert) -> &mut ActiveAlert {
+        let now = Utc::now();
+        let entry = self.active.entry(ActiveKey::of(alert)).or_insert_with(|| ActiveAlert {
+            alert: alert.clone(),
+            first_seen: now,
+            last_seen: now,
+            occurrences: 0,
+            critical_since: None,
+            notified: false,
+            acknowledged: false,
+        });
+        entry.last_seen = now;
+        entry.occurrences += 1;
+        alert.started_at = Some(entry.first_seen);
+        entry
+    }
+
+    /// Store the alert as finally dispatched and track time spent at Critical
+    pub fn update(&mut self, alert: &Alert) {
+        if let Some(entry) = self.active.get_mut(&ActiveKey::of(alert)) {
+            if alert.severity >= Severity::Critical {
+                entry.critical_since.get_or_insert(alert.timestamp);
+            } else {
+                entry.critical_since = None;
+            }
+            entry.alert = alert.clone();
+        }
+    }
+
+    pub fn mark_notified(&mut self, alert: &Alert) {
+        if let Some(entry) = self.active.get_mut(&ActiveKey::of(alert)) {
+            entry.notified = true;
+        }
+    }
+
+    /// Mark a notified condition acknowledged; true the first time only
+    pub fn acknowledge(&mut self, alert: &Alert) -> bool {
+        match self.active.get_mut(&ActiveKey::of(alert)) {
+            Some(entry) if entry.notified && !entry.acknowledged => {
+                entry.acknowledged = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ActiveAlert> {
+        self.active.values()
+    }
+
+    /// Take over conditions saved before a restart. They count as seen now,
+    /// so one still holding is re-raised by its analyzer before it could
+    /// clear, while one that cleared during the downtime resolves after
+    /// `clear_secs`.
+    pub fn restore(&mut self, entries: Vec<ActiveAlert>) {
+        let now = Utc::now();
+        for mut entry in entries {
+            entry.last_seen = entry.last_seen.max(now);
+            self.active.insert(ActiveKey::of(&entry.alert), entry);
+        }
+    }
+
+    /// Remove and return conditions that stayed clear long enough
+    pub fn take_cleared(&mut self) -> Vec<ActiveAlert> {
+        let now = Utc::now();
+        let cleared: Vec<ActiveKey> = self.active.iter()
+            .filter(|(_, a)| (now - a.last_seen).num_seconds() >= self.clear_secs)
+            .map(|(k, _)| k.clone())
+            .collect();
+        cleared.into_iter()
+            .filter_map(|k| self.active.remove(&k))
+            .collect()
+    }
+}
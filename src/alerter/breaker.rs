@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::AlertingConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Normal operation
+    Closed,
+    /// Failing, sends are rejected without being attempted
+    Open,
+    /// Testing whether the channel recovered
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub const ALL: [CircuitState; 3] = [CircuitState::Closed, CircuitState::Open, CircuitState::HalfOpen];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Returned instead of sending while a channel's breaker is open
+#[derive(Debug, thiserror::Error)]
+#[error("circuit breaker open")]
+pub struct CircuitOpen;
+
+#[derive(Debug, Clone)]
+pub struct CircuitConfig {
+    pub failure_threshold: u32,
+    pub recovery_time: Duration,
+    pub success_threshold: u32,
+}
+
+impl CircuitConfig {
+    pub fn new(config: &AlertingConfig) -> Self {
+        Self {
+            failure_threshold: config.breaker_failure_threshold.max(1),
+            recovery_time: Duration::from_secs(config.breaker_recovery_secs),
+            success_threshold: config.breaker_success_threshold.max(1),
+        }
+    }
+}
+
+/// Stops sending to a channel after repeated failures so a dead endpoint
+/// costs nothing until it has had time to recover (DESIGN.md §15.2)
+pub struct CircuitBreaker {
+    channel: String,
+    state: CircuitState,
+    /// Consecutive failures while closed
+    failure_count: u32,
+    /// Consecutive successes while half-open
+    success_count: u32,
+    last_failure: Option<Instant>,
+    config: CircuitConfig,
+    failures_total: u64,
+    last_success_at: Option<DateTime<Utc>>,
+    last_failure_at: Option<DateTime<Utc>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(channel: &str, config: CircuitConfig) -> Self {
+        Self {
+            channel: channel.to_string(),
+            state: CircuitState::Closed,
+            failure_count: 0,
+            success_count: 0,
+            last_failure: None,
+            config,
+            failures_total: 0,
+            last_success_at: None,
+            last_failure_at: None,
+        }
+    }
+
+    /// Whether a send may be attempted. An open breaker turns half-open once
+    /// `recovery_time` has passed since the last failure.
+    pub fn allow(&mut self) -> bool {
+        if self.state == CircuitState::Open {
+            if self.last_failure.is_some_and(|t| t.elapsed() < self.config.recovery_time) {
+                return false;
+            }
+            info!(channel = %self.channel, "Circuit breaker half-open, trying channel again");
+            self.state = CircuitState::HalfOpen;
+            self.success_count = 0;
+        }
+        true
+    }
+
+    pub fn on_success(&mut self) {
+        self.last_success_at = Some(Utc::now());
+        match self.state {
+            CircuitState::HalfOpen => {
+                self.success_count += 1;
+                if self.success_count >= self.config.success_threshold {
+                    info!(channel = %self.channel, "Circuit breaker closed");
+                    self.state = CircuitState::Closed;
+                    self.failure_count = 0;
+                }
+            }
+            CircuitState::Closed => self.failure_count = 0,
+            CircuitState::Open => {}
+        }
+    }
+
+    pub fn on_failure(&mut self) {
+        self.failure_count += 1;
+        self.failures_total += 1;
+        self.last_failure = Some(Instant::now());
+        self.last_failure_at = Some(Utc::now());
+
+        // A failed trial reopens straight away
+        if self.state == CircuitState::HalfOpen
+            || (self.state == CircuitState::Closed && self.failure_count >= self.config.failure_threshold)
+        {
+            warn!(
+                channel = %self.channel,
+                failures = self.failure_count,
+                retry_in_secs = self.config.recovery_time.as_secs(),
+                "Circuit breaker opened"
+            );
+            self.state = CircuitState::Open;
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.failure_count
+    }
+
+    pub fn failures_total(&self) -> u64 {
+        self.failures_total
+    }
+
+    pub fn last_success(&self) -> Option<DateTime<Utc>> {
+        self.last_success_at
+    }
+
+    pub fn last_failure(&self) -> Option<DateTime<Utc>> {
+        self.last_failure_at
+    }
+}
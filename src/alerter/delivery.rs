@@ -0,0 +1,723 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::hash::BuildHasher;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+use crate::config::AlertingConfig;
+use crate::types::Alert;
+use super::breaker::{CircuitBreaker, CircuitConfig, CircuitOpen, CircuitState};
+use super::journal::{Journal, Outcome};
+use super::persist::FileWriter;
+use super::AlertChannel;
+
+/// Directory in `data_dir` holding one dead-letter file per channel
+const DEAD_LETTER_DIR: &str = "dead_letters";
+/// Notifications waiting for a channel's worker. When full, new ones are
+/// dead-lettered instead of stalling the dispatch loop.
+const QUEUE_CAPACITY: usize = 256;
+/// Longest `Retry-After` honored; longer waits are clamped
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+/// Alerts in one batched notification at most; the rest start the next batch
+const MAX_BATCH: usize = 100;
+/// Names the dead-letter files in persistence errors
+const DEAD_LETTERS: &str = "dead-lettered alerts";
+
+/// HTTP 429 from a channel's endpoint, with the wait it asked for
+#[derive(Debug, thiserror::Error)]
+#[error("rate limited by the server (HTTP 429)")]
+pub struct RateLimited {
+    pub retry_after: Option<Duration>,
+}
+
+/// A request body the channel could not build for an alert, e.g. a body
+/// template that does not render valid JSON
+#[derive(Debug, thiserror::Error)]
+#[error("invalid payload: {0}")]
+pub struct InvalidPayload(pub String);
+
+/// Like `error_for_status`, but a 429 becomes `RateLimited` carrying the
+/// response's `Retry-After` (delay in seconds or an HTTP date)
+pub fn check_status(resp: reqwest::Response) -> Result<reqwest::Response> {
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = resp.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        return Err(RateLimited { retry_after }.into());
+    }
+    Ok(resp.error_for_status()?)
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).ok();
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// Invalid payloads, client errors other than timeouts and rate limiting, and
+/// permanent SMTP replies will fail the same way however often they are retried
+fn is_permanent(e: &anyhow::Error) -> bool {
+    e.is::<InvalidPayload>() || e.downcast_ref::<reqwest::Error>()
+        .and_then(|e| e.status())
+        .is_some_and(|s| {
+            s.is_client_error()
+                && s != reqwest::StatusCode::REQUEST_TIMEOUT
+                && s != reqwest::StatusCode::TOO_MANY_REQUESTS
+        })
+        || e.downcast_ref::<lettre::transport::smtp::Error>().is_some_and(|e| e.is_permanent())
+}
+
+#[derive(Debug, Clone)]
+struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    replay_interval: Duration,
+    max_dead_letters: usize,
+    max_age: chrono::Duration,
+}
+
+impl RetryPolicy {
+    fn new(config: &AlertingConfig) -> Self {
+        Self {
+            max_attempts: config.retry_max_attempts.max(1),
+            backoff: Duration::from_secs(config.retry_backoff_secs),
+            max_backoff: Duration::from_secs(config.retry_max_backoff_secs),
+            replay_interval: Duration::from_secs(config.dead_letter_replay_secs.max(1)),
+            max_dead_letters: config.dead_letter_max_entries,
+            max_age: chrono::Duration::hours(config.dead_letter_max_age_hours as i64),
+        }
+    }
+
+    /// Wait before retry number `attempt` (1-based): exponential, capped, and
+    /// with the upper half jittered so channels failing together don't retry
+    /// in lockstep
+    fn delay(&self, attempt: u32) -> Duration {
+        let base = self.backoff
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_backoff);
+        let half = base / 2;
+        // RandomState is seeded differently on every call, which is plenty for jitter
+        let random = std::collections::hash_map::RandomState::new().hash_one(attempt);
+        half + half.mul_f64((random >> 11) as f64 / (1u64 << 53) as f64)
+    }
+}
+
+/// What a queued notification asks of the channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// Send the alerts, firing or resolved
+    #[default]
+    Notify,
+    /// Acknowledge the alerts' incidents (channels with `acknowledges`)
+    Acknowledge,
+}
+
+/// A notification that could not be delivered
+#[derive(Serialize, Deserialize)]
+struct DeadLetter {
+    id: u64,
+    failed_at: DateTime<Utc>,
+    error: String,
+    #[serde(default)]
+    event: Event,
+    alerts: Vec<Alert>,
+}
+
+/// A channel's undelivered notifications, oldest first, persisted in
+/// `data_dir` so they survive restarts
+struct DeadLetters {
+    entries: VecDeque<DeadLetter>,
+    next_id: u64,
+    path: PathBuf,
+    max_entries: usize,
+    max_age: chrono::Duration,
+    channel: String,
+    journal: Arc<Journal>,
+    writer: FileWriter,
+}
+
+impl DeadLetters {
+    fn load(
+        channel: &str,
+        data_dir: &str,
+        policy: &RetryPolicy,
+        journal: Arc<Journal>,
+        writer: FileWriter,
+    ) -> Result<Self> {
+        // Instance names are user-chosen; keep them from escaping the directory
+        let file: String = channel.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = PathBuf::from(data_dir).join(DEAD_LETTER_DIR).join(format!("{}.json", file));
+        let entries: VecDeque<DeadLetter> = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        if !entries.is_empty() {
+            info!(channel, pending = entries.len(), "Loaded dead-lettered alerts");
+        }
+
+        Ok(Self {
+            next_id: entries.iter().map(|d| d.id + 1).max().unwrap_or(0),
+            entries,
+            path,
+            max_entries: policy.max_dead_letters,
+            max_age: policy.max_age,
+            channel: channel.to_string(),
+            journal,
+            writer,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn push(&mut self, event: Event, alerts: Vec<Alert>, error: String) {
+        self.journal.record_delivery(&self.channel, &alerts, Outcome::DeadLettered, Some(&error));
+        self.entries.push_back(DeadLetter {
+            id: self.next_id,
+            failed_at: Utc::now(),
+            error,
+            event,
+            alerts,
+        });
+        self.next_id += 1;
+        while self.entries.len() > self.max_entries {
+            if let Some(dropped) = self.entries.pop_front() {
+                self.journal.record_delivery(&self.channel, &dropped.alerts, Outcome::Expired, Some("dead-letter queue full"));
+            }
+        }
+        self.save();
+    }
+
+    /// Oldest notification still worth replaying
+    fn oldest(&mut self) -> Option<(u64, Event, Vec<Alert>)> {
+        let cutoff = Utc::now() - self.max_age;
+        let before = self.entries.len();
+        for expired in self.entries.iter().filter(|d| d.failed_at < cutoff) {
+            self.journal.record_delivery(&self.channel, &expired.alerts, Outcome::Expired, Some(&expired.error));
+        }
+        self.entries.retain(|d| d.failed_at >= cutoff);
+        if self.entries.len() != before {
+            warn!(path = %self.path.display(), dropped = before - self.entries.len(), "Dropped expired dead-lettered alerts");
+            self.save();
+        }
+        self.entries.front().map(|d| (d.id, d.event, d.alerts.clone()))
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.entries.retain(|d| d.id != id);
+        self.save();
+    }
+
+    /// Queue the entries for the file writer; an empty queue removes the file
+    fn save(&self) {
+        if self.entries.is_empty() {
+            self.writer.remove(&self.path, DEAD_LETTERS);
+            return;
+        }
+        match serde_json::to_vec(&self.entries) {
+            Ok(contents) => self.writer.save(&self.path, contents, DEAD_LETTERS),
+            Err(e) => error!(error = %e, path = %self.path.display(), "Failed to serialize dead-lettered alerts"),
+        }
+    }
+}
+
+/// A channel's delivery state as reported in self-metrics and the heartbeat
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelHealth {
+    pub channel: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub failures_total: u64,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub dead_letters: usize,
+}
+
+/// One queued notification
+struct Job {
+    event: Event,
+    alerts: Vec<Alert>,
+    /// Handed over by another channel's breaker; never passed on again
+    failed_over: bool,
+}
+
+/// Hands notifications to a channel's background worker, which sends them
+/// in order with retries so a slow or failing channel never holds up the
+/// alert manager or the other channels
+#[derive(Clone)]
+pub struct Outbox {
+    channel: Arc<dyn AlertChannel>,
+    tx: mpsc::Sender<Job>,
+    dead_letters: Arc<Mutex<DeadLetters>>,
+    breaker: Arc<Mutex<CircuitBreaker>>,
+}
+
+impl Outbox {
+    /// Create the queue for `channel` along with the worker that drains it.
+    /// The worker is started separately so outboxes can fail over to each other.
+    pub fn new(
+        channel: Arc<dyn AlertChannel>,
+        config: &AlertingConfig,
+        data_dir: &str,
+        journal: Arc<Journal>,
+        writer: FileWriter,
+    ) -> Result<(Self, Worker)> {
+        let policy = RetryPolicy::new(config);
+        let dead_letters = DeadLetters::load(channel.name(), data_dir, &policy, journal.clone(), writer)?;
+        let dead_letters = Arc::new(Mutex::new(dead_letters));
+        let breaker = Arc::new(Mutex::new(CircuitBreaker::new(channel.name(), CircuitConfig::new(config))));
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        let worker = Worker {
+            channel: channel.clone(),
+            policy,
+            dead_letters: dead_letters.clone(),
+            breaker: breaker.clone(),
+            fallback: None,
+            journal,
+            rate_limited_until: None,
+            held: None,
+            rx,
+        };
+        Ok((Self { channel, tx, dead_letters, breaker }, worker))
+    }
+
+    /// Queue a notification: one alert is sent on its own, several as a group
+    /// if the channel sends groups
+    pub fn submit(&self, alerts: Vec<Alert>) {
+        self.enqueue(Job { event: Event::Notify, alerts, failed_over: false });
+    }
+
+    /// Queue an acknowledgement of the alert's incident
+    pub fn acknowledge(&self, alert: Alert) {
+        self.enqueue(Job { event: Event::Acknowledge, alerts: vec![alert], failed_over: false });
+    }
+
+    fn enqueue(&self, job: Job) {
+        if job.alerts.len() > 1 && job.event == Event::Notify && !self.channel.sends_groups() {
+            for alert in job.alerts {
+                self.enqueue(Job { event: job.event, alerts: vec![alert], failed_over: job.failed_over });
+            }
+            return;
+        }
+        let (mpsc::error::TrySendError::Full(job) | mpsc::error::TrySendError::Closed(job)) =
+            match self.tx.try_send(job) {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+        error!(channel = self.channel.name(), alerts = job.alerts.len(), "Delivery queue full, alert dead-lettered");
+        self.dead_letters.lock().unwrap().push(job.event, job.alerts, "delivery queue full".into());
+    }
+
+    pub fn health(&self) -> ChannelHealth {
+        let breaker = self.breaker.lock().unwrap();
+        ChannelHealth {
+            channel: self.channel.name().to_string(),
+            state: breaker.state(),
+            consecutive_failures: breaker.consecutive_failures(),
+            failures_total: breaker.failures_total(),
+            last_success: breaker.last_success(),
+            last_failure: breaker.last_failure(),
+            dead_letters: self.dead_letters.lock().unwrap().len(),
+        }
+    }
+}
+
+pub struct Worker {
+    channel: Arc<dyn AlertChannel>,
+    policy: RetryPolicy,
+    dead_letters: Arc<Mutex<DeadLetters>>,
+    breaker: Arc<Mutex<CircuitBreaker>>,
+    /// Takes notifications this channel cannot send while its breaker is open
+    fallback: Option<Outbox>,
+    journal: Arc<Journal>,
+    /// End of a `Retry-After` wait too long to sleep through; nothing is
+    /// sent before then, new notifications are dead-lettered for replay
+    rate_limited_until: Option<Instant>,
+    /// Taken off the queue while batching but not part of the batch
+    held: Option<Job>,
+    rx: mpsc::Receiver<Job>,
+}
+
+impl Worker {
+    /// Start sending; must be called within a Tokio runtime
+    pub fn start(mut self, fallback: Option<Outbox>) {
+        self.fallback = fallback;
+        tokio::spawn(self.run());
+    }
+
+    async fn run(mut self) {
+        if !self.dead_letters.lock().unwrap().is_empty() {
+            self.replay().await;
+        }
+        loop {
+            let pending = !self.dead_letters.lock().unwrap().is_empty();
+            let next = if let Some(job) = self.held.take() {
+                Some(job)
+            } else if pending {
+                // Probe the channel periodically even when nothing new arrives
+                match tokio::time::timeout(self.policy.replay_interval, self.rx.recv()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        self.replay().await;
+                        continue;
+                    }
+                }
+            } else {
+                self.rx.recv().await
+            };
+            let Some(mut job) = next else { break };
+
+            if self.rate_limited() {
+                self.fail(job, RateLimited { retry_after: None }.into());
+                continue;
+            }
+            if let Some(window) = self.channel.batch_window().filter(|_| job.event == Event::Notify) {
+                self.collect_batch(&mut job, window).await;
+            }
+            match self.send_with_retry(job.event, &job.alerts).await {
+                Ok(()) => {
+                    self.record_sent(job.event, &job.alerts, None);
+                    // The channel is reachable again
+                    if pending {
+                        self.replay().await;
+                    }
+                }
+                Err(e) => {
+                    self.note_rate_limit(&e);
+                    self.fail(job, e);
+                }
+            }
+        }
+    }
+
+    /// Add the notifications queued within `window` to `job`, up to `MAX_BATCH` alerts
+    async fn collect_batch(&mut self, job: &mut Job, window: Duration) {
+        let deadline = tokio::time::Instant::now() + window;
+        while job.alerts.len() < MAX_BATCH {
+            match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                Ok(Some(next))
+                    if next.event == job.event
+                        && next.failed_over == job.failed_over
+                        && job.alerts.len() + next.alerts.len() <= MAX_BATCH =>
+                {
+                    job.alerts.extend(next.alerts);
+                }
+                Ok(Some(next)) => {
+                    self.held = Some(next);
+                    break;
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+    }
+
+    /// Whether the server asked for a wait that has not passed yet
+    fn rate_limited(&self) -> bool {
+        self.rate_limited_until.is_some_and(|t| Instant::now() < t)
+    }
+
+    /// Hold off sending for as long as a 429 asked, up to `MAX_RETRY_AFTER`
+    fn note_rate_limit(&mut self, e: &anyhow::Error) {
+        if let Some(RateLimited { retry_after: Some(wait) }) = e.downcast_ref::<RateLimited>() {
+            let wait = (*wait).min(MAX_RETRY_AFTER);
+            warn!(channel = self.channel.name(), wait_secs = wait.as_secs(), "Channel rate limited, holding off");
+            self.rate_limited_until = Some(Instant::now() + wait);
+        }
+    }
+
+    /// Fail over, drop or dead-letter a notification that could not be sent
+    fn fail(&self, job: Job, e: anyhow::Error) {
+        let channel = self.channel.name();
+        // Replaying would only be rejected the same way
+        if is_permanent(&e) {
+            error!(channel, alerts = job.alerts.len(), error = %format!("{:#}", e), "Alert rejected by channel, dropped");
+            self.journal.record_delivery(channel, &job.alerts, Outcome::Rejected, Some(&format!("{:#}", e)));
+            return;
+        }
+
+        let mut alerts = job.alerts;
+        let open = self.breaker.lock().unwrap().state() == CircuitState::Open;
+        // Acknowledgements only mean something to the channel holding the incident
+        let movable = open && !job.failed_over && job.event == Event::Notify;
+        if let Some(fallback) = self.fallback.as_ref().filter(|_| movable) {
+            let (taken, rest): (Vec<Alert>, Vec<Alert>) = alerts.into_iter()
+                .partition(|a| fallback.channel.accepts_severity(&a.severity));
+            if !taken.is_empty() {
+                warn!(channel, fallback = fallback.channel.name(), alerts = taken.len(), "Channel unavailable, failing over");
+                self.journal.record_delivery(channel, &taken, Outcome::FailedOver, Some(fallback.channel.name()));
+                fallback.enqueue(Job { event: Event::Notify, alerts: taken, failed_over: true });
+            }
+            if rest.is_empty() {
+                return;
+            }
+            alerts = rest;
+        }
+
+        error!(channel, alerts = alerts.len(), error = %format!("{:#}", e), "Failed to send alert, dead-lettered for replay");
+        self.dead_letters.lock().unwrap().push(job.event, alerts, format!("{:#}", e));
+    }
+
+    fn record_sent(&self, event: Event, alerts: &[Alert], detail: Option<&str>) {
+        let outcome = match event {
+            Event::Notify => Outcome::Delivered,
+            Event::Acknowledge => Outcome::Acknowledged,
+        };
+        self.journal.record_delivery(self.channel.name(), alerts, outcome, detail);
+    }
+
+    /// One send through the circuit breaker
+    async fn send(&self, event: Event, alerts: &[Alert]) -> Result<()> {
+        if !self.breaker.lock().unwrap().allow() {
+            return Err(CircuitOpen.into());
+        }
+        let result = match (event, alerts) {
+            (Event::Acknowledge, alerts) => self.acknowledge(alerts).await,
+            (Event::Notify, [alert]) => self.channel.send(alert).await,
+            (Event::Notify, batch) => self.channel.send_batch(batch).await,
+        };
+        let mut breaker = self.breaker.lock().unwrap();
+        match result {
+            Ok(()) => breaker.on_success(),
+            Err(_) => breaker.on_failure(),
+        }
+        result
+    }
+
+    async fn acknowledge(&self, alerts: &[Alert]) -> Result<()> {
+        for alert in alerts {
+            self.channel.acknowledge(alert).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_with_retry(&self, event: Event, alerts: &[Alert]) -> Result<()> {
+        let mut attempt = 1;
+        loop {
+            let e = match self.send(event, alerts).await {
+                Ok(()) => {
+                    if attempt > 1 {
+                        info!(channel = self.channel.name(), attempt, "Alert delivered after retrying");
+                    }
+                    return Ok(());
+                }
+                Err(e) => e,
+            };
+            if attempt >= self.policy.max_attempts
+                || is_permanent(&e)
+                || self.breaker.lock().unwrap().state() == CircuitState::Open
+            {
+                return Err(e);
+            }
+
+            let delay = match e.downcast_ref::<RateLimited>() {
+                // Sleeping longer would hold up the queue behind this notification
+                Some(RateLimited { retry_after: Some(wait) }) if *wait > self.policy.max_backoff => return Err(e),
+                Some(RateLimited { retry_after: Some(wait) }) => *wait,
+                _ => self.policy.delay(attempt),
+            };
+            warn!(
+                channel = self.channel.name(),
+                attempt,
+                retry_in_ms = delay.as_millis() as u64,
+                error = %format!("{:#}", e),
+                "Failed to send alert, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send dead-lettered notifications oldest first, stopping at the first
+    /// that fails for a reason that may pass
+    async fn replay(&mut self) {
+        if self.rate_limited() {
+            return;
+        }
+        let mut replayed = 0;
+        loop {
+            let Some((id, event, alerts)) = self.dead_letters.lock().unwrap().oldest() else { break };
+            match self.send(event, &alerts).await {
+                Ok(()) => {
+                    self.record_sent(event, &alerts, Some("replayed"));
+                    replayed += 1;
+                }
+                Err(e) if is_permanent(&e) => {
+                    error!(channel = self.channel.name(), error = %format!("{:#}", e), "Dropping undeliverable dead-lettered alert");
+                    self.journal.record_delivery(self.channel.name(), &alerts, Outcome::Rejected, Some(&format!("{:#}", e)));
+                }
+                Err(e) => {
+                    debug!(channel = self.channel.name(), error = %format!("{:#}", e), "Channel still failing, keeping dead-lettered alerts");
+                    self.note_rate_limit(&e);
+                    break;
+                }
+            }
+            self.dead_letters.lock().unwrap().remove(id);
+        }
+        if replayed > 0 {
+            info!(channel = self.channel.name(), replayed, "Replayed dead-lettered alerts");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use crate::types::{AlertStatus, MetricId, Severity};
+
+    fn alert() -> Alert {
+        Alert {
+            timestamp: Utc::now(),
+            severity: Severity::Warn,
+            metric: MetricId::CpuUsage,
+            value: 91.0,
+            threshold: Some(90.0),
+            message: "CPU usage 91.0%".into(),
+            labels: Default::default(),
+            hostname: "web-01".into(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 1.5 "), Some(Duration::from_millis(1500)));
+        let at = (Utc::now() + chrono::Duration::seconds(90)).to_rfc2822();
+        let wait = parse_retry_after(&at).unwrap();
+        assert!(wait > Duration::from_secs(85) && wait <= Duration::from_secs(90));
+        // Dates in the past and garbage carry no wait
+        assert_eq!(parse_retry_after(&(Utc::now() - chrono::Duration::seconds(90)).to_rfc2822()), None);
+        assert_eq!(parse_retry_after("-5"), None);
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap_with_jitter() {
+        let config: AlertingConfig = toml::from_str("retry_backoff_secs = 2\nretry_max_backoff_secs = 10").unwrap();
+        let policy = RetryPolicy::new(&config);
+        for (attempt, base) in [(1, 2), (2, 4), (3, 8), (4, 10), (30, 10)] {
+            let base = Duration::from_secs(base);
+            for _ in 0..20 {
+                let delay = policy.delay(attempt);
+                assert!(delay >= base / 2 && delay <= base, "attempt {}: {:?}", attempt, delay);
+            }
+        }
+    }
+
+    /// Answers every send with HTTP 429 and a ten-minute Retry-After
+    struct RateLimitedChannel {
+        sends: AtomicU32,
+    }
+
+    #[async_trait]
+    impl AlertChannel for RateLimitedChannel {
+        fn name(&self) -> &str { "limited" }
+
+        async fn send(&self, _alert: &Alert) -> Result<()> {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+            Err(RateLimited { retry_after: Some(Duration::from_secs(600)) }.into())
+        }
+
+        fn accepts_severity(&self, _severity: &Severity) -> bool { true }
+    }
+
+    /// Records the size of every notification, batching for 300ms
+    struct BatchingChannel {
+        sent: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl AlertChannel for BatchingChannel {
+        fn name(&self) -> &str { "batching" }
+
+        fn sends_groups(&self) -> bool { true }
+
+        fn batch_window(&self) -> Option<Duration> { Some(Duration::from_millis(300)) }
+
+        async fn send(&self, _alert: &Alert) -> Result<()> {
+            self.sent.lock().unwrap().push(1);
+            Ok(())
+        }
+
+        async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+            self.sent.lock().unwrap().push(alerts.len());
+            Ok(())
+        }
+
+        fn accepts_severity(&self, _severity: &Severity) -> bool { true }
+    }
+
+    #[tokio::test]
+    async fn batches_notifications_queued_within_the_window() {
+        let config: AlertingConfig = toml::from_str("journal_enabled = false").unwrap();
+        let channel = Arc::new(BatchingChannel { sent: Mutex::new(Vec::new()) });
+        let journal = Arc::new(Journal::new(&config, "/nonexistent").unwrap());
+        let (outbox, worker) = Outbox::new(channel.clone(), &config, "/nonexistent", journal, FileWriter::spawn().unwrap()).unwrap();
+        worker.start(None);
+
+        for _ in 0..150 {
+            outbox.submit(vec![alert()]);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        outbox.submit(vec![alert(), alert()]);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while channel.sent.lock().unwrap().iter().sum::<usize>() < 152 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Capped at 100 per notification; the rest shares the next window
+        assert_eq!(*channel.sent.lock().unwrap(), [100, 52]);
+    }
+
+    #[tokio::test]
+    async fn long_retry_after_dead_letters_instead_of_waiting() {
+        let dir = std::env::temp_dir().join(format!("sysops-agent-retry-after-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap();
+        let config: AlertingConfig = toml::from_str("retry_max_backoff_secs = 5\njournal_enabled = false").unwrap();
+        let channel = Arc::new(RateLimitedChannel { sends: AtomicU32::new(0) });
+        let journal = Arc::new(Journal::new(&config, data_dir).unwrap());
+        let files = FileWriter::spawn().unwrap();
+        let (outbox, worker) = Outbox::new(channel.clone(), &config, data_dir, journal, files.clone()).unwrap();
+        worker.start(None);
+
+        outbox.submit(vec![alert()]);
+        outbox.submit(vec![alert()]);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while outbox.health().dead_letters < 2 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::task::spawn_blocking(move || files.flush()).await.unwrap();
+        assert!(dir.join(DEAD_LETTER_DIR).join("limited.json").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Both dead-lettered at once; the second was not sent during the wait
+        assert_eq!(outbox.health().dead_letters, 2);
+        assert_eq!(channel.sends.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,19 +3,27 @@ use async_trait::async_trait;
 use serde_json::json;
 
 use crate::config::DiscordConfig;
-use crate::types::{Alert, Severity};
-use super::AlertChannel;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::{no_escape, Template};
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+const RESOLVED_COLOR: u32 = 0x2ECC71; // green
+/// Discord rejects messages with more than 10 embeds
+const MAX_EMBEDS: usize = 10;
 
 /// Discord webhook alert channel
 pub struct DiscordChannel {
+    name: String,
     webhook_url: String,
     username: String,
     severity_filter: Vec<Severity>,
+    template: Option<Template>,
     client: reqwest::Client,
 }
 
 impl DiscordChannel {
-    pub fn new(config: &DiscordConfig) -> Result<Self> {
+    pub fn new(config: &DiscordConfig, template: Option<Template>) -> Result<Self> {
         let severity_filter = config.severity_filter.iter()
             .filter_map(|s| match s.as_str() {
                 "info" => Some(Severity::Info),
@@ -27,13 +35,54 @@ impl DiscordChannel {
             .collect();
 
         Ok(Self {
+            name: config.name.clone(),
             webhook_url: config.webhook_url.clone(),
             username: config.username.clone().unwrap_or_else(|| "SysOps Agent".into()),
             severity_filter,
-            client: reqwest::Client::new(),
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    fn embed(&self, alert: &Alert) -> serde_json::Value {
+        let color = match alert.status {
+            AlertStatus::Firing => Self::severity_color(&alert.severity),
+            AlertStatus::Resolved => RESOLVED_COLOR,
+        };
+        if let Some(ref template) = self.template {
+            return json!({
+                "description": template.render(alert, no_escape),
+                "color": color,
+                "timestamp": alert.timestamp.to_rfc3339(),
+            });
+        }
+
+        let mut fields = vec![
+            json!({ "name": "Host", "value": &alert.hostname, "inline": true }),
+            json!({ "name": "Metric", "value": alert.metric.to_string(), "inline": true }),
+            json!({ "name": "Value", "value": format!("{:.2}", alert.value), "inline": true }),
+        ];
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            fields.push(json!({ "name": "Duration", "value": format_duration(d), "inline": true }));
+        }
+
+        json!({
+            "title": format!("{} {}", status_tag(alert), alert.message),
+            "color": color,
+            "fields": fields,
+            "timestamp": alert.timestamp.to_rfc3339(),
         })
     }
 
+    async fn post(&self, payload: &serde_json::Value) -> Result<()> {
+        let resp = self.client.post(&self.webhook_url)
+            .json(payload)
+            .send()
+            .await?;
+        check_status(resp)?;
+        Ok(())
+    }
+
     fn severity_color(severity: &Severity) -> u32 {
         match severity {
             Severity::Info => 0x2ECC71,      // green
@@ -46,33 +95,34 @@ impl DiscordChannel {
 
 #[async_trait]
 impl AlertChannel for DiscordChannel {
-    fn name(&self) -> &str { "discord" }
+    fn name(&self) -> &str { &self.name }
 
     fn accepts_severity(&self, severity: &Severity) -> bool {
         self.severity_filter.is_empty() || self.severity_filter.contains(severity)
     }
 
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
     async fn send(&self, alert: &Alert) -> Result<()> {
         let payload = json!({
             "username": self.username,
-            "embeds": [{
-                "title": format!("[{}] {}", alert.severity, alert.message),
-                "color": Self::severity_color(&alert.severity),
-                "fields": [
-                    { "name": "Host", "value": &alert.hostname, "inline": true },
-                    { "name": "Metric", "value": alert.metric.to_string(), "inline": true },
-                    { "name": "Value", "value": format!("{:.2}", alert.value), "inline": true },
-                ],
-                "timestamp": alert.timestamp.to_rfc3339(),
-            }]
+            "embeds": [self.embed(alert)],
         });
+        self.post(&payload).await
+    }
 
-        self.client.post(&self.webhook_url)
-            .json(&payload)
-            .send()
-            .await?
-            .error_for_status()?;
-
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let summary = group_summary(alerts);
+        for chunk in alerts.chunks(MAX_EMBEDS) {
+            let payload = json!({
+                "username": self.username,
+                "content": format!("**{}**", summary),
+                "embeds": chunk.iter().map(|a| self.embed(a)).collect::<Vec<_>>(),
+            });
+            self.post(&payload).await?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,247 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::extension::ClientId;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+use crate::config::EmailConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::template::{no_escape, Template};
+use super::{format_duration, group_summary, status_tag, AlertChannel};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+const RESOLVED_COLOR: &str = "#2ecc71";
+
+/// SMTP alert channel; a group of alerts, or the alerts queued within
+/// `batch_window_secs` of each other, is sent as a single message
+pub struct EmailChannel {
+    name: String,
+    severity_filter: Vec<Severity>,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    template: Option<Template>,
+    batch_window: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TlsMode {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    StartTls,
+    /// TLS from the first byte (SMTPS, usually port 465)
+    Implicit,
+    /// No encryption; only for local relays and test sinks
+    None,
+}
+
+impl EmailChannel {
+    pub fn new(config: &EmailConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        let tls_mode = match config.tls_mode.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Some("starttls") => TlsMode::StartTls,
+            Some("tls") | Some("implicit") | Some("smtps") => TlsMode::Implicit,
+            Some("none") | Some("plain") => TlsMode::None,
+            Some(other) => bail!("Unsupported email tls_mode: {} (expected starttls, tls or none)", other),
+            // Legacy `smtp_tls` flag: port 465 means SMTPS, otherwise STARTTLS
+            None if !config.smtp_tls => TlsMode::None,
+            None if config.smtp_port == 465 => TlsMode::Implicit,
+            None => TlsMode::StartTls,
+        };
+
+        let mut builder = match tls_mode {
+            TlsMode::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?,
+            TlsMode::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)?,
+            TlsMode::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host),
+        }
+        .port(config.smtp_port)
+        .timeout(Some(SMTP_TIMEOUT));
+
+        if let Ok(name) = hostname::get() {
+            builder = builder.hello_name(ClientId::Domain(name.to_string_lossy().into_owned()));
+        }
+        if !config.username.is_empty() {
+            builder = builder.credentials(Credentials::new(config.username.clone(), config.password.clone()));
+        }
+
+        let from: Mailbox = config.from.parse()
+            .with_context(|| format!("Invalid email from address: {}", config.from))?;
+        let to = config.to.iter()
+            .map(|addr| addr.parse().with_context(|| format!("Invalid email recipient: {}", addr)))
+            .collect::<Result<Vec<Mailbox>>>()?;
+        if to.is_empty() {
+            bail!("Email channel requires at least one recipient in `to`");
+        }
+
+        Ok(Self {
+            name: config.name.clone(),
+            severity_filter,
+            transport: builder.build(),
+            from,
+            to,
+            template,
+            batch_window: (config.batch_window_secs > 0).then(|| Duration::from_secs(config.batch_window_secs)),
+        })
+    }
+
+    async fn send_message(&self, batch: &[Alert]) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject(batch));
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let (plain, html) = match self.template {
+            Some(ref template) => {
+                let text = batch.iter()
+                    .map(|a| template.render(a, no_escape))
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                (format!("{}\n\n-- \nSent by sysops-agent\n", text), template_html_body(batch, &text))
+            }
+            None => (plain_body(batch), html_body(batch)),
+        };
+        let message = builder.multipart(MultiPart::alternative_plain_html(plain, html))?;
+
+        self.transport.send(message).await.context("SMTP delivery failed")?;
+        Ok(())
+    }
+}
+
+fn subject(batch: &[Alert]) -> String {
+    let worst = batch.iter()
+        .filter(|a| a.status == AlertStatus::Firing)
+        .map(|a| a.severity)
+        .max();
+    match (batch, worst) {
+        ([alert], _) => format!("{} {}: {}", status_tag(alert), alert.hostname, alert.message),
+        (_, Some(worst)) => format!("[{}] {}", worst, group_summary(batch)),
+        (_, None) => format!("[RESOLVED] {}", group_summary(batch)),
+    }
+}
+
+fn labels_text(alert: &Alert) -> String {
+    alert.labels.iter()
+        .map(|l| format!("{}={}", l.key, l.value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn plain_body(batch: &[Alert]) -> String {
+    let mut out = String::new();
+    for alert in batch {
+        out.push_str(&format!("{} {}\n", status_tag(alert), alert.message));
+        out.push_str(&format!("  Host:      {}\n", alert.hostname));
+        out.push_str(&format!("  Metric:    {}\n", alert.metric));
+        out.push_str(&format!("  Value:     {:.2}\n", alert.value));
+        if let Some(threshold) = alert.threshold {
+            out.push_str(&format!("  Threshold: {:.2}\n", threshold));
+        }
+        if !alert.labels.is_empty() {
+            out.push_str(&format!("  Labels:    {}\n", labels_text(alert)));
+        }
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            out.push_str(&format!("  Duration:  {}\n", format_duration(d)));
+        }
+        out.push_str(&format!("  Time:      {}\n\n", alert.timestamp.to_rfc3339()));
+    }
+    out.push_str("-- \nSent by sysops-agent\n");
+    out
+}
+
+fn html_body(batch: &[Alert]) -> String {
+    let mut rows = String::new();
+    for alert in batch {
+        rows.push_str(&format!(
+            "<tr><td style=\"color:{};font-weight:bold\">{}</td><td>{}</td><td>{}</td>\
+             <td>{:.2}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            match alert.status {
+                AlertStatus::Firing => severity_color(&alert.severity),
+                AlertStatus::Resolved => RESOLVED_COLOR,
+            },
+            match alert.status {
+                AlertStatus::Firing => alert.severity.to_string(),
+                AlertStatus::Resolved => match alert.active_duration() {
+                    Some(d) => format!("RESOLVED after {}", format_duration(d)),
+                    None => "RESOLVED".to_string(),
+                },
+            },
+            escape_html(&alert.message),
+            escape_html(&alert.metric.to_string()),
+            alert.value,
+            alert.threshold.map(|t| format!("{:.2}", t)).unwrap_or_default(),
+            escape_html(&labels_text(alert)),
+            alert.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+        ));
+    }
+    format!(
+        "<html><body>\n<h3>{}</h3>\n\
+         <table border=\"1\" cellpadding=\"4\" cellspacing=\"0\" style=\"border-collapse:collapse\">\n\
+         <tr><th>Severity</th><th>Message</th><th>Metric</th><th>Value</th><th>Threshold</th><th>Labels</th><th>Time</th></tr>\n\
+         {}</table>\n<p style=\"color:#888\">Sent by sysops-agent</p>\n</body></html>\n",
+        escape_html(&subject(batch)),
+        rows,
+    )
+}
+
+/// HTML part for templated emails: the rendered text, preformatted
+fn template_html_body(batch: &[Alert], text: &str) -> String {
+    format!(
+        "<html><body>\n<h3>{}</h3>\n\
+         <pre style=\"font-family:inherit;white-space:pre-wrap\">{}</pre>\n\
+         <p style=\"color:#888\">Sent by sysops-agent</p>\n</body></html>\n",
+        escape_html(&subject(batch)),
+        escape_html(text),
+    )
+}
+
+fn severity_color(severity: &Severity) -> &'static str {
+    match severity {
+        Severity::Info => "#2ecc71",
+        Severity::Warn => "#f39c12",
+        Severity::Critical => "#e74c3c",
+        Severity::Emergency => "#9b59b6",
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[async_trait]
+impl AlertChannel for EmailChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    fn batch_window(&self) -> Option<Duration> {
+        self.batch_window
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.send_message(std::slice::from_ref(alert)).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        self.send_message(alerts).await
+    }
+}
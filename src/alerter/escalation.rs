@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+
+use crate::config::EscalationConfig;
+use crate::types::{Alert, Label, MetricId, Severity};
+use super::active::ActiveAlert;
+
+/// One escalation policy; the first policy matching an alert applies
+struct EscalationPolicy {
+    metric: Option<MetricId>,
+    labels: Vec<Label>,
+    warn_to_critical_after: Option<u32>,
+    critical_to_emergency_after: Option<chrono::Duration>,
+}
+
+impl EscalationPolicy {
+    fn matches(&self, alert: &Alert) -> bool {
+        self.metric.is_none_or(|m| m == alert.metric)
+            && self.labels.iter().all(|want| alert.labels.iter().any(|l| l == want))
+    }
+}
+
+/// Raises the severity of conditions that persist: Warn becomes Critical
+/// after N consecutive occurrences, Critical becomes Emergency after N
+/// minutes unresolved. State lives in the active alert, so it resets as
+/// soon as the condition clears.
+pub struct Escalator {
+    policies: Vec<EscalationPolicy>,
+}
+
+impl Escalator {
+    pub fn new(config: &[EscalationConfig]) -> Result<Self> {
+        let policies = config.iter()
+            .map(|c| {
+                let metric = c.metric.as_deref()
+                    .map(|m| m.parse::<MetricId>().map_err(anyhow::Error::msg))
+                    .transpose()
+                    .context("Invalid metric in escalation policy")?;
+                let mut labels: Vec<Label> = c.labels.iter()
+                    .map(|(k, v)| Label { key: k.clone(), value: v.clone() })
+                    .collect();
+                labels.sort();
+                Ok(EscalationPolicy {
+                    metric,
+                    labels,
+                    warn_to_critical_after: c.warn_to_critical_after,
+                    critical_to_emergency_after: c.critical_to_emergency_after_mins
+                        .map(|m| chrono::Duration::minutes(m as i64)),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { policies })
+    }
+
+    /// Escalate `alert` based on the condition's history
+    pub fn apply(&self, alert: &mut Alert, state: &ActiveAlert) {
+        let Some(policy) = self.policies.iter().find(|p| p.matches(alert)) else { return };
+        let original = alert.severity;
+
+        if alert.severity == Severity::Warn
+            && policy.warn_to_critical_after.is_some_and(|n| state.occurrences >= n)
+        {
+            alert.severity = Severity::Critical;
+        }
+
+        if alert.severity == Severity::Critical {
+            if let Some(after) = policy.critical_to_emergency_after {
+                let since = state.critical_since.unwrap_or(alert.timestamp);
+                if Utc::now() - since >= after {
+                    alert.severity = Severity::Emergency;
+                }
+            }
+        }
+
+        if alert.severity != original {
+            alert.escalated_from = Some(original);
+            alert.message = format!("{} (escalated from {})", alert.message, original);
+            // The condition is raised again on every pass; log only the transition
+            if alert.severity != state.alert.severity {
+                tracing::info!(
+                    metric = %alert.metric,
+                    from = %original,
+                    to = %alert.severity,
+                    occurrences = state.occurrences,
+                    "Alert escalated"
+                );
+            }
+        }
+    }
+}
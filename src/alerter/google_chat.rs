@@ -0,0 +1,140 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::config::GoogleChatConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::Template;
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+const RESOLVED_COLOR: &str = "#2ecc71"; // green
+
+/// Google Chat space webhook alert channel, posting cards (`cardsV2`)
+pub struct GoogleChatChannel {
+    name: String,
+    webhook_url: String,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    client: reqwest::Client,
+}
+
+impl GoogleChatChannel {
+    pub fn new(config: &GoogleChatConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name.clone(),
+            webhook_url: config.webhook_url.clone(),
+            severity_filter,
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    /// One alert as a card section. Card text widgets accept a small HTML
+    /// subset, which is how the severity color is applied.
+    fn section(&self, alert: &Alert) -> serde_json::Value {
+        let color = match alert.status {
+            AlertStatus::Firing => Self::severity_color(&alert.severity),
+            AlertStatus::Resolved => RESOLVED_COLOR,
+        };
+        if let Some(ref template) = self.template {
+            return json!({
+                "widgets": [{ "textParagraph": { "text": template.render(alert, escape_html) } }],
+            });
+        }
+
+        let title = format!(
+            "<font color=\"{}\"><b>{}</b></font> {}",
+            color,
+            status_tag(alert),
+            escape_html(&alert.message),
+        );
+        let mut widgets = vec![
+            json!({ "textParagraph": { "text": title } }),
+            json!({ "decoratedText": { "topLabel": "Host", "text": escape_html(&alert.hostname) } }),
+            json!({ "decoratedText": { "topLabel": "Metric", "text": escape_html(&alert.metric.to_string()) } }),
+            json!({ "decoratedText": { "topLabel": "Value", "text": format!("{:.2}", alert.value) } }),
+        ];
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            widgets.push(json!({ "decoratedText": { "topLabel": "Duration", "text": format_duration(d) } }));
+        }
+        widgets.push(json!({ "decoratedText": { "topLabel": "Time", "text": alert.timestamp.to_rfc3339() } }));
+
+        json!({ "widgets": widgets })
+    }
+
+    /// `text` is what notifications and clients without card support show
+    fn message(text: String, title: String, sections: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "text": text,
+            "cardsV2": [{
+                "cardId": "sysops-alert",
+                "card": {
+                    "header": { "title": title, "subtitle": "SysOps Agent" },
+                    "sections": sections,
+                },
+            }],
+        })
+    }
+
+    async fn post(&self, payload: &serde_json::Value) -> Result<()> {
+        let resp = self.client.post(&self.webhook_url)
+            .json(payload)
+            .send()
+            .await?;
+        check_status(resp)?;
+        Ok(())
+    }
+
+    fn severity_color(severity: &Severity) -> &'static str {
+        match severity {
+            Severity::Info => "#2ecc71",
+            Severity::Warn => "#f39c12",
+            Severity::Critical => "#e74c3c",
+            Severity::Emergency => "#9b59b6",
+        }
+    }
+}
+
+/// Card text is parsed as HTML
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[async_trait]
+impl AlertChannel for GoogleChatChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let text = format!("{} {}", status_tag(alert), alert.message);
+        let payload = Self::message(text, alert.hostname.clone(), vec![self.section(alert)]);
+        self.post(&payload).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let summary = group_summary(alerts);
+        let payload = Self::message(summary.clone(), summary, alerts.iter().map(|a| self.section(a)).collect());
+        self.post(&payload).await
+    }
+}
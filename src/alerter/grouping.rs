@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::types::Alert;
+
+/// Alerts sharing a host and either the configured label values or, when no
+/// labels are configured, the metric family (`disk` for `disk.usage_percent`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct GroupKey {
+    hostname: String,
+    group: Vec<String>,
+}
+
+struct PendingGroup {
+    opened: Instant,
+    alerts: Vec<Alert>,
+}
+
+/// Buffers alerts for `window` after the first alert of a group arrives,
+/// so a burst of related alerts goes out as one notification
+pub struct AlertGrouper {
+    window: Duration,
+    group_by: Vec<String>,
+    groups: HashMap<GroupKey, PendingGroup>,
+}
+
+impl AlertGrouper {
+    pub fn new(window_secs: u64, group_by: &[String]) -> Self {
+        Self {
+            window: Duration::from_secs(window_secs),
+            group_by: group_by.to_vec(),
+            groups: HashMap::new(),
+        }
+    }
+
+    fn key(&self, alert: &Alert) -> GroupKey {
+        let group = if self.group_by.is_empty() {
+            vec![metric_family(alert)]
+        } else {
+            self.group_by.iter()
+                .map(|name| {
+                    alert.labels.iter()
+                        .find(|l| &l.key == name)
+                        .map(|l| l.value.clone())
+                        .unwrap_or_default()
+                })
+                .collect()
+        };
+        GroupKey { hostname: alert.hostname.clone(), group }
+    }
+
+    pub fn add(&mut self, alert: Alert) {
+        let key = self.key(&alert);
+        self.groups.entry(key)
+            .or_insert_with(|| PendingGroup { opened: Instant::now(), alerts: Vec::new() })
+            .alerts
+            .push(alert);
+    }
+
+    /// Remove and return the groups whose window has elapsed
+    pub fn take_due(&mut self) -> Vec<Vec<Alert>> {
+        let due: Vec<GroupKey> = self.groups.iter()
+            .filter(|(_, g)| g.opened.elapsed() >= self.window)
+            .map(|(k, _)| k.clone())
+            .collect();
+        due.into_iter()
+            .filter_map(|k| self.groups.remove(&k))
+            .map(|g| g.alerts)
+            .collect()
+    }
+}
+
+/// First component of the metric name, e.g. `disk` for `disk.usage_percent`
+pub fn metric_family(alert: &Alert) -> String {
+    let name = alert.metric.to_string();
+    match name.split_once('.') {
+        Some((family, _)) => family.to_string(),
+        None => name,
+    }
+}
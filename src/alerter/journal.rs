@@ -0,0 +1,540 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::time::Duration;
+
+use crate::config::AlertingConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+
+/// Directory in `data_dir` holding the journal and its rotated files
+pub const JOURNAL_DIR: &str = "journal";
+const JOURNAL_FILE: &str = "alerts.jsonl";
+/// Entries queued between the alert tasks and the writer thread
+const QUEUE_CAPACITY: usize = 10_000;
+/// How long a query waits for queued entries to be written
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What happened to an alert, as recorded in the journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Passed deduplication and was handed to routing (or a group)
+    Fired,
+    /// A RESOLVED notification for a cleared condition
+    Resolved,
+    /// Suppressed as a repeat of a recently notified condition
+    Deduplicated,
+    Silenced,
+    /// Dropped by a channel's rate limits
+    RateLimited,
+    /// No channel was routed the alert or accepts its severity
+    Unrouted,
+    Delivered,
+    Acknowledged,
+    /// Handed to the fallback channel while this one's breaker was open
+    FailedOver,
+    /// Could not be delivered; kept for replay
+    DeadLettered,
+    /// Rejected by the channel (client error), not retried
+    Rejected,
+    /// Dropped from the dead-letter queue unsent, too old or crowded out
+    Expired,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 12] = [
+        Outcome::Fired, Outcome::Resolved, Outcome::Deduplicated, Outcome::Silenced,
+        Outcome::RateLimited, Outcome::Unrouted, Outcome::Delivered, Outcome::Acknowledged,
+        Outcome::FailedOver, Outcome::DeadLettered, Outcome::Rejected, Outcome::Expired,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Fired => "fired",
+            Outcome::Resolved => "resolved",
+            Outcome::Deduplicated => "deduplicated",
+            Outcome::Silenced => "silenced",
+            Outcome::RateLimited => "rate_limited",
+            Outcome::Unrouted => "unrouted",
+            Outcome::Delivered => "delivered",
+            Outcome::Acknowledged => "acknowledged",
+            Outcome::FailedOver => "failed_over",
+            Outcome::DeadLettered => "dead_lettered",
+            Outcome::Rejected => "rejected",
+            Outcome::Expired => "expired",
+        }
+    }
+}
+
+impl std::str::FromStr for Outcome {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let name = s.trim().to_lowercase().replace('-', "_");
+        Outcome::ALL.into_iter()
+            .find(|o| o.as_str() == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown outcome `{}`", s))
+    }
+}
+
+/// One journal line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub time: DateTime<Utc>,
+    pub outcome: Outcome,
+    /// Channel instance, for per-channel outcomes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    pub hostname: String,
+    pub metric: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    pub severity: Severity,
+    pub status: AlertStatus,
+    pub source: String,
+    pub message: String,
+    /// Silence ID, error, fallback channel, ... depending on the outcome
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl JournalEntry {
+    fn new(alert: &Alert, outcome: Outcome, channel: Option<&str>, detail: Option<String>) -> Self {
+        Self {
+            time: Utc::now(),
+            outcome,
+            channel: channel.map(str::to_owned),
+            hostname: alert.hostname.clone(),
+            metric: alert.metric.to_string(),
+            labels: alert.labels.iter().map(|l| (l.key.clone(), l.value.clone())).collect(),
+            severity: alert.severity,
+            status: alert.status,
+            source: alert.source.clone(),
+            message: alert.message.clone(),
+            detail,
+        }
+    }
+}
+
+/// Append-only record of every alert decision and delivery outcome, as
+/// JSON lines in `{data_dir}/journal/alerts.jsonl`. The file is rotated to
+/// `alerts.jsonl.1`, `.2`, ... once it reaches `journal_max_file_mb`.
+/// Entries are queued to a writer thread, so recording never blocks.
+pub struct Journal {
+    dir: PathBuf,
+    tx: Option<SyncSender<WriterMsg>>,
+    dropped: AtomicU64,
+}
+
+/// Messages to the writer thread
+enum WriterMsg {
+    Entry(Box<JournalEntry>),
+    /// Acknowledge once everything queued before has been written
+    Flush(Sender<()>),
+}
+
+/// Owns the journal file, on the writer thread
+struct JournalWriter {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    max_files: u32,
+    file: Option<JournalFile>,
+}
+
+struct JournalFile {
+    file: File,
+    size: u64,
+}
+
+impl Journal {
+    pub fn new(config: &AlertingConfig, data_dir: &str) -> Result<Self> {
+        let dir = PathBuf::from(data_dir).join(JOURNAL_DIR);
+        let tx = if config.journal_enabled {
+            let writer = JournalWriter {
+                dir: dir.clone(),
+                max_file_bytes: config.journal_max_file_mb.max(1) * 1024 * 1024,
+                max_files: config.journal_max_files.max(1),
+                file: None,
+            };
+            let (tx, rx) = sync_channel(QUEUE_CAPACITY);
+            std::thread::Builder::new()
+                .name("journal-writer".into())
+                .spawn(move || writer.run(rx))?;
+            Some(tx)
+        } else {
+            None
+        };
+        Ok(Self { dir, tx, dropped: AtomicU64::new(0) })
+    }
+
+    pub fn record(&self, alert: &Alert, outcome: Outcome) {
+        self.write(JournalEntry::new(alert, outcome, None, None));
+    }
+
+    pub fn record_detail(&self, alert: &Alert, outcome: Outcome, detail: impl Into<String>) {
+        self.write(JournalEntry::new(alert, outcome, None, Some(detail.into())));
+    }
+
+    /// Record a per-channel outcome for each alert of a notification
+    pub fn record_delivery(&self, channel: &str, alerts: &[Alert], outcome: Outcome, detail: Option<&str>) {
+        for alert in alerts {
+            self.write(JournalEntry::new(alert, outcome, Some(channel), detail.map(str::to_owned)));
+        }
+    }
+
+    /// Queue an entry without blocking; if the writer falls behind, entries are dropped
+    fn write(&self, entry: JournalEntry) {
+        let Some(tx) = &self.tx else { return };
+        if tx.try_send(WriterMsg::Entry(Box::new(entry))).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % 1000 == 1 {
+                tracing::warn!(dropped, "Alert journal queue full, dropping entries");
+            }
+        }
+    }
+
+    /// Wait until the entries queued so far are written.
+    /// Blocks, so call it off the async runtime.
+    pub fn flush(&self) {
+        let Some(tx) = &self.tx else { return };
+        let (done_tx, done_rx) = channel();
+        if tx.send(WriterMsg::Flush(done_tx)).is_err() {
+            return;
+        }
+        if done_rx.recv_timeout(FLUSH_TIMEOUT).is_err() {
+            tracing::warn!("Timed out flushing the alert journal");
+        }
+    }
+
+    /// Entries matching the query, newest first, including those still
+    /// queued. Entries written before the journal was disabled can still be
+    /// queried. Reads the journal files, so call it off the async runtime.
+    pub fn query(&self, query: &JournalQuery) -> Result<Vec<JournalEntry>> {
+        self.flush();
+        read(&self.dir, query)
+    }
+}
+
+impl JournalWriter {
+    fn run(mut self, rx: Receiver<WriterMsg>) {
+        for msg in rx {
+            match msg {
+                WriterMsg::Entry(entry) => self.write(&entry),
+                WriterMsg::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, entry: &JournalEntry) {
+        let mut line = match serde_json::to_vec(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to serialize journal entry");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        if let Err(e) = self.append(&line) {
+            tracing::error!(error = %e, dir = %self.dir.display(), "Failed to write alert journal");
+            // Reopen on the next entry
+            self.file = None;
+        }
+    }
+
+    fn append(&mut self, line: &[u8]) -> Result<()> {
+        if self.file.as_ref().is_some_and(|f| f.size > 0 && f.size + line.len() as u64 > self.max_file_bytes) {
+            self.file = None;
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            std::fs::create_dir_all(&self.dir)
+                .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+            let handle = OpenOptions::new().create(true).append(true).open(self.dir.join(JOURNAL_FILE))?;
+            let size = handle.metadata()?.len();
+            self.file = Some(JournalFile { file: handle, size });
+        }
+        let current = self.file.as_mut().expect("journal file opened above");
+        current.file.write_all(line)?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shift `alerts.jsonl.N` to `.N+1`, dropping the oldest beyond `max_files`
+    fn rotate(&self) -> Result<()> {
+        let path = |n: u32| rotated_path(&self.dir, n);
+        if self.max_files == 1 {
+            std::fs::remove_file(path(0))?;
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(path(self.max_files - 1));
+        for n in (1..self.max_files - 1).rev() {
+            if path(n).exists() {
+                std::fs::rename(path(n), path(n + 1))?;
+            }
+        }
+        std::fs::rename(path(0), path(1))?;
+        Ok(())
+    }
+}
+
+/// `alerts.jsonl` for 0, `alerts.jsonl.N` for rotated files
+fn rotated_path(dir: &Path, n: u32) -> PathBuf {
+    match n {
+        0 => dir.join(JOURNAL_FILE),
+        n => dir.join(format!("{}.{}", JOURNAL_FILE, n)),
+    }
+}
+
+/// Journal filter, from the CLI or a `query_journal` control request.
+/// Times are RFC 3339 or relative to now (`90m`, `12h`, `7d`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct JournalQuery {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Severities to include; all when empty
+    pub severity: Vec<String>,
+    /// Metric name, or metric family such as `disk`
+    pub metric: Option<String>,
+    /// Outcomes to include; all when empty
+    pub outcome: Vec<String>,
+    pub channel: Option<String>,
+    /// Most recent entries returned; 100 when unset
+    pub limit: Option<usize>,
+}
+
+const DEFAULT_LIMIT: usize = 100;
+
+/// A parsed `JournalQuery`
+struct Filter {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    severities: Vec<Severity>,
+    metric: Option<String>,
+    outcomes: Vec<Outcome>,
+    channel: Option<String>,
+}
+
+impl Filter {
+    fn new(query: &JournalQuery) -> Result<Self> {
+        let severities = query.severity.iter()
+            .map(|s| match s.trim().to_lowercase().as_str() {
+                "info" => Ok(Severity::Info),
+                "warn" => Ok(Severity::Warn),
+                "critical" => Ok(Severity::Critical),
+                "emergency" => Ok(Severity::Emergency),
+                _ => bail!("Unknown severity `{}`", s),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let outcomes = query.outcome.iter()
+            .map(|o| o.parse())
+            .collect::<Result<Vec<Outcome>>>()?;
+
+        Ok(Self {
+            since: query.since.as_deref().map(parse_time).transpose()?,
+            until: query.until.as_deref().map(parse_time).transpose()?,
+            severities,
+            metric: query.metric.clone(),
+            outcomes,
+            channel: query.channel.clone(),
+        })
+    }
+
+    fn matches(&self, e: &JournalEntry) -> bool {
+        self.since.is_none_or(|t| e.time >= t)
+            && self.until.is_none_or(|t| e.time <= t)
+            && (self.severities.is_empty() || self.severities.contains(&e.severity))
+            && (self.outcomes.is_empty() || self.outcomes.contains(&e.outcome))
+            && self.channel.as_ref().is_none_or(|c| e.channel.as_ref() == Some(c))
+            && self.metric.as_ref().is_none_or(|m| {
+                e.metric == *m || e.metric.strip_prefix(m.as_str()).is_some_and(|rest| rest.starts_with('.'))
+            })
+    }
+}
+
+/// RFC 3339, or a duration before now: `30s`, `90m`, `12h`, `7d`
+fn parse_time(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let ago = match (n.parse::<i64>(), unit) {
+        (Ok(n), "s") => chrono::Duration::seconds(n),
+        (Ok(n), "m") => chrono::Duration::minutes(n),
+        (Ok(n), "h") => chrono::Duration::hours(n),
+        (Ok(n), "d") => chrono::Duration::days(n),
+        _ => bail!("Invalid time `{}`: use RFC 3339 or a duration ago such as 12h", s),
+    };
+    Ok(Utc::now() - ago)
+}
+
+/// Entries of the journal in `data_dir` matching the query, newest first.
+/// Reads the files directly, so it also works while the agent is stopped.
+pub fn query(data_dir: &str, query: &JournalQuery) -> Result<Vec<JournalEntry>> {
+    read(&PathBuf::from(data_dir).join(JOURNAL_DIR), query)
+}
+
+fn read(dir: &Path, query: &JournalQuery) -> Result<Vec<JournalEntry>> {
+    let filter = Filter::new(query)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+
+    let mut entries = Vec::new();
+    // Newest file first; stop at the first file older than `since`
+    for n in 0.. {
+        let path = rotated_path(dir, n);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let mut reached_since = false;
+        for line in content.lines().rev() {
+            // A line cut short by a crash or an older format is skipped
+            let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else { continue };
+            if filter.since.is_some_and(|t| entry.time < t) {
+                reached_since = true;
+                break;
+            }
+            if filter.matches(&entry) {
+                entries.push(entry);
+                if entries.len() >= limit {
+                    return Ok(entries);
+                }
+            }
+        }
+        if reached_since {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MetricId;
+
+    fn alert(metric: MetricId, message: &str) -> Alert {
+        Alert {
+            timestamp: Utc::now(),
+            severity: Severity::Warn,
+            metric,
+            value: 91.0,
+            threshold: Some(90.0),
+            message: message.into(),
+            labels: Default::default(),
+            hostname: "web-01".into(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sysops-agent-journal-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// A writer that rotates before every entry but the first of a file
+    fn writer(dir: &Path, max_files: u32) -> JournalWriter {
+        JournalWriter { dir: dir.to_path_buf(), max_file_bytes: 1, max_files, file: None }
+    }
+
+    fn messages(dir: &Path, query: &JournalQuery) -> Vec<String> {
+        read(dir, query).unwrap().into_iter().map(|e| e.message).collect()
+    }
+
+    fn write(writer: &mut JournalWriter, entry: JournalEntry) {
+        let mut line = serde_json::to_vec(&entry).unwrap();
+        line.push(b'\n');
+        writer.append(&line).unwrap();
+    }
+
+    #[test]
+    fn rotation_keeps_the_entry_with_a_single_file() {
+        let dir = temp_dir("single");
+        let mut writer = writer(&dir, 1);
+        for message in ["first", "second", "third"] {
+            write(&mut writer, JournalEntry::new(&alert(MetricId::CpuUsage, message), Outcome::Fired, None, None));
+        }
+
+        assert_eq!(messages(&dir, &JournalQuery::default()), ["third"]);
+        assert!(!rotated_path(&dir, 1).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotation_drops_files_beyond_max_files() {
+        let dir = temp_dir("rotate");
+        let mut writer = writer(&dir, 3);
+        for message in ["1", "2", "3", "4", "5"] {
+            write(&mut writer, JournalEntry::new(&alert(MetricId::CpuUsage, message), Outcome::Fired, None, None));
+        }
+
+        assert_eq!(messages(&dir, &JournalQuery::default()), ["5", "4", "3"]);
+        assert!(!rotated_path(&dir, 3).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn query_filters_newest_first() {
+        let dir = temp_dir("query");
+        let mut writer = JournalWriter { dir: dir.clone(), max_file_bytes: u64::MAX, max_files: 1, file: None };
+        let mut old = JournalEntry::new(&alert(MetricId::DiskUsage, "old"), Outcome::Fired, None, None);
+        old.time = Utc::now() - chrono::Duration::hours(2);
+        write(&mut writer, old);
+        write(&mut writer, JournalEntry::new(&alert(MetricId::DiskUsage, "disk"), Outcome::Fired, None, None));
+        write(&mut writer, JournalEntry::new(&alert(MetricId::DiskInodeUsage, "inodes"), Outcome::Deduplicated, None, None));
+        write(&mut writer, JournalEntry::new(&alert(MetricId::CpuUsage, "cpu"), Outcome::Delivered, Some("slack"), None));
+        write(&mut writer, JournalEntry::new(&alert(MetricId::DiskUsage, "delivered"), Outcome::Delivered, Some("email"), None));
+
+        let all = JournalQuery::default();
+        assert_eq!(messages(&dir, &all), ["delivered", "cpu", "inodes", "disk", "old"]);
+        let recent = JournalQuery { since: Some("1h".into()), ..Default::default() };
+        assert_eq!(messages(&dir, &recent), ["delivered", "cpu", "inodes", "disk"]);
+        let family = JournalQuery { metric: Some("disk".into()), since: Some("1h".into()), ..Default::default() };
+        assert_eq!(messages(&dir, &family), ["delivered", "inodes", "disk"]);
+        let exact = JournalQuery { metric: Some("disk.usage_percent".into()), limit: Some(2), ..Default::default() };
+        assert_eq!(messages(&dir, &exact), ["delivered", "disk"]);
+        let delivered = JournalQuery { outcome: vec!["delivered".into()], channel: Some("slack".into()), ..Default::default() };
+        assert_eq!(messages(&dir, &delivered), ["cpu"]);
+        let bad = JournalQuery { severity: vec!["loud".into()], ..Default::default() };
+        assert!(read(&dir, &bad).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_absolute_and_relative_times() {
+        let t = parse_time("2026-01-02T03:04:05+02:00").unwrap();
+        assert_eq!(t, DateTime::parse_from_rfc3339("2026-01-02T01:04:05Z").unwrap());
+
+        let ago = Utc::now() - parse_time(" 90m ").unwrap();
+        assert!((ago - chrono::Duration::minutes(90)).num_seconds().abs() <= 1);
+        let ago = Utc::now() - parse_time("7d").unwrap();
+        assert!((ago - chrono::Duration::days(7)).num_seconds().abs() <= 1);
+
+        for invalid in ["", "12", "h", "5w", "-3h", "yesterday"] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_outcome_names() {
+        assert_eq!("dead-lettered".parse::<Outcome>().unwrap(), Outcome::DeadLettered);
+        assert_eq!(" Rate_Limited".parse::<Outcome>().unwrap(), Outcome::RateLimited);
+        assert!("lost".parse::<Outcome>().is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::config::MattermostConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::{no_escape, Template};
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+const RESOLVED_COLOR: &str = "#2ecc71"; // green
+
+/// Mattermost incoming webhook alert channel. The payload is Slack-compatible
+/// attachments plus Mattermost's own `username`, `icon_url` and `props`.
+pub struct MattermostChannel {
+    name: String,
+    webhook_url: String,
+    channel: Option<String>,
+    username: String,
+    icon_url: Option<String>,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    client: reqwest::Client,
+}
+
+impl MattermostChannel {
+    pub fn new(config: &MattermostConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name.clone(),
+            webhook_url: config.webhook_url.clone(),
+            channel: config.channel.clone(),
+            username: config.username.clone().unwrap_or_else(|| "SysOps Agent".into()),
+            icon_url: config.icon_url.clone(),
+            severity_filter,
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    fn attachment(&self, alert: &Alert) -> serde_json::Value {
+        let color = match alert.status {
+            AlertStatus::Firing => Self::severity_color(&alert.severity),
+            AlertStatus::Resolved => RESOLVED_COLOR,
+        };
+        // Shown in notifications and clients that cannot render attachments
+        let fallback = format!("{} {} on {}", status_tag(alert), alert.message, alert.hostname);
+        if let Some(ref template) = self.template {
+            return json!({
+                "fallback": fallback,
+                "color": color,
+                "text": template.render(alert, no_escape),
+            });
+        }
+
+        let mut fields = vec![
+            json!({ "title": "Host", "value": &alert.hostname, "short": true }),
+            json!({ "title": "Metric", "value": alert.metric.to_string(), "short": true }),
+            json!({ "title": "Value", "value": format!("{:.2}", alert.value), "short": true }),
+        ];
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            fields.push(json!({ "title": "Duration", "value": format_duration(d), "short": true }));
+        }
+
+        json!({
+            "fallback": fallback,
+            "color": color,
+            "title": format!("{} {}", status_tag(alert), alert.message),
+            "fields": fields,
+            "footer": alert.timestamp.to_rfc3339(),
+        })
+    }
+
+    /// Markdown shown in the post's info panel, with the full alert details
+    fn card(alerts: &[Alert]) -> String {
+        let mut card = String::from("| Host | Metric | Labels | Value | Threshold | Analyzer |\n|---|---|---|---|---|---|\n");
+        for alert in alerts {
+            let labels: Vec<String> = alert.labels.iter().map(|l| format!("{}={}", l.key, l.value)).collect();
+            card.push_str(&format!(
+                "| {} | {} | {} | {:.2} | {} | {} |\n",
+                alert.hostname,
+                alert.metric,
+                labels.join(", ").replace('|', "\\|"),
+                alert.value,
+                alert.threshold.map(|t| format!("{:.2}", t)).unwrap_or_default(),
+                alert.source,
+            ));
+        }
+        card
+    }
+
+    async fn post(&self, mut payload: serde_json::Value, alerts: &[Alert]) -> Result<()> {
+        payload["username"] = json!(self.username);
+        if let Some(ref url) = self.icon_url {
+            payload["icon_url"] = json!(url);
+        }
+        if let Some(ref ch) = self.channel {
+            payload["channel"] = json!(ch);
+        }
+        payload["props"] = json!({ "card": Self::card(alerts) });
+
+        let resp = self.client.post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?;
+        check_status(resp)?;
+
+        Ok(())
+    }
+
+    fn severity_color(severity: &Severity) -> &'static str {
+        match severity {
+            Severity::Info => "#2ecc71",
+            Severity::Warn => "#f39c12",
+            Severity::Critical => "#e74c3c",
+            Severity::Emergency => "#9b59b6",
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for MattermostChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let payload = json!({
+            "attachments": [self.attachment(alert)],
+        });
+        self.post(payload, std::slice::from_ref(alert)).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let payload = json!({
+            "text": format!("**{}**", group_summary(alerts)),
+            "attachments": alerts.iter().map(|a| self.attachment(a)).collect::<Vec<_>>(),
+        });
+        self.post(payload, alerts).await
+    }
+}
@@ -1,14 +1,38 @@
+pub mod active;
+pub mod breaker;
+pub mod delivery;
 pub mod discord;
+pub mod email;
+pub mod escalation;
+pub mod google_chat;
+pub mod grouping;
+pub mod journal;
+pub mod mattermost;
+pub mod ntfy;
+pub mod pagerduty;
+mod persist;
+pub mod rate_limit;
+pub mod routing;
+pub mod silence;
 pub mod slack;
+mod state;
+pub mod syslog;
+pub mod teams;
+pub mod telegram;
+pub mod template;
 pub mod webhook;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use std::collections::HashMap;
-use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
 
-use crate::config::AlertingConfig;
-use crate::types::{Alert, MetricId, Severity};
+use crate::config::{AlertingConfig, SilenceConfig};
+use crate::types::{Alert, AlertStatus, Severity};
 
 /// Trait for alert delivery channels
 #[async_trait]
@@ -19,136 +43,809 @@ pub trait AlertChannel: Send + Sync {
     /// Send a single alert
     async fn send(&self, alert: &Alert) -> Result<()>;
 
+    /// Whether `send_batch` sends a group as one notification. Groups for
+    /// channels that don't are queued one alert per job, so a failed send is
+    /// retried without repeating the alerts already delivered.
+    fn sends_groups(&self) -> bool {
+        false
+    }
+
+    /// How long the delivery worker holds a notification to send the ones
+    /// queued meanwhile along with it, as one `send_batch`. Only for
+    /// channels that send groups.
+    fn batch_window(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Send a group of related alerts as one notification. Channels without
+    /// a grouped format fall back to sending each alert on its own.
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let mut result = Ok(());
+        for alert in alerts {
+            if let Err(e) = self.send(alert).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
     /// Check if this channel accepts the given severity
     fn accepts_severity(&self, severity: &Severity) -> bool;
+
+    /// Whether the channel tracks incidents that can be acknowledged
+    fn acknowledges(&self) -> bool {
+        false
+    }
+
+    /// Acknowledge the incident opened by a firing alert
+    async fn acknowledge(&self, _alert: &Alert) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Runtime command for the alert manager, received as JSON from the control
+/// socket or NATS, e.g. `{"command": "remove_silence", "id": "config-0"}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    AddSilence { silence: SilenceConfig },
+    RemoveSilence { id: String },
+    ListSilences,
+    /// Journal entries, newest first
+    QueryJournal {
+        #[serde(default)]
+        query: journal::JournalQuery,
+    },
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub silences: Option<Vec<silence::SilenceStatus>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<Vec<journal::JournalEntry>>,
+}
+
+impl ControlResponse {
+    pub fn error(e: impl std::fmt::Display) -> Self {
+        Self { error: Some(e.to_string()), ..Default::default() }
+    }
+
+    fn from_result(result: Result<ControlResponse>) -> Self {
+        match result {
+            Ok(response) => Self { ok: true, ..response },
+            Err(e) => Self::error(format!("{:#}", e)),
+        }
+    }
+}
+
+/// A control request with the channel its response goes back on
+pub struct ControlMessage {
+    pub request: ControlRequest,
+    pub reply: oneshot::Sender<ControlResponse>,
 }
 
 /// Manages alert dispatch, rate limiting, and deduplication
 pub struct AlertManager {
-    channels: Vec<Box<dyn AlertChannel>>,
+    channels: Vec<Arc<dyn AlertChannel>>,
+    outboxes: Vec<delivery::Outbox>,
     dedup_map: HashMap<DeduplicationKey, DedupEntry>,
     dedup_window_secs: u64,
-    rate_limiter: RateLimiter,
+    repeat_interval_secs: u64,
+    last_cleanup: Instant,
+    limiters: Vec<rate_limit::ChannelLimiter>,
+    active: active::ActiveAlerts,
+    recovery_enabled: bool,
+    escalator: escalation::Escalator,
+    grouper: Option<grouping::AlertGrouper>,
+    silences: silence::Silences,
+    router: routing::Router,
+    journal: Arc<journal::Journal>,
+    /// Saves the alert state and dead letters off the async runtime
+    files: persist::FileWriter,
+    state_file: state::StateFile,
+    /// Whether the state changed since it was last saved
+    state_dirty: bool,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// How often expired deduplication entries and silences are dropped
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// One condition (host, series and analyzer) at one severity
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 struct DeduplicationKey {
-    metric: MetricId,
+    hostname: String,
+    condition: active::ActiveKey,
     severity: Severity,
 }
 
+impl DeduplicationKey {
+    fn of(alert: &Alert) -> Self {
+        Self {
+            hostname: alert.hostname.clone(),
+            condition: active::ActiveKey::of(alert),
+            severity: alert.severity,
+        }
+    }
+}
+
 struct DedupEntry {
     last_sent: Instant,
+    last_seen: Instant,
+    /// Occurrences since the last notification
     count: u32,
 }
 
-struct RateLimiter {
-    tokens: f64,
-    max_tokens: f64,
-    refill_rate: f64,
-    last_refill: Instant,
-}
+impl AlertManager {
+    /// Create the channels and start their delivery workers; must be called
+    /// within a Tokio runtime
+    pub fn new(config: &AlertingConfig, data_dir: &str) -> Result<Self> {
+        let names = channel_names(config)?;
+        let templates = template::Templates::new(config, &names)?;
+        let mut channels: Vec<Box<dyn AlertChannel>> = Vec::new();
 
-impl RateLimiter {
-    fn new(per_minute: u32) -> Self {
-        Self {
-            tokens: per_minute as f64,
-            max_tokens: per_minute as f64,
-            refill_rate: per_minute as f64 / 60.0,
-            last_refill: Instant::now(),
+        for dc in config.discord.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&dc.name, "discord");
+            channels.push(Box::new(discord::DiscordChannel::new(dc, template)?));
         }
-    }
 
-    fn try_acquire(&mut self) -> bool {
-        let now = Instant::now();
-        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
-        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.max_tokens);
-        self.last_refill = now;
+        for sc in config.slack.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&sc.name, "slack");
+            channels.push(Box::new(slack::SlackChannel::new(sc, template)?));
+        }
 
-        if self.tokens >= 1.0 {
-            self.tokens -= 1.0;
-            true
-        } else {
-            false
+        for tc in config.telegram.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&tc.name, "telegram");
+            channels.push(Box::new(telegram::TelegramChannel::new(tc, template)?));
         }
-    }
-}
 
-impl AlertManager {
-    pub fn new(config: &AlertingConfig) -> Result<Self> {
-        let mut channels: Vec<Box<dyn AlertChannel>> = Vec::new();
+        for ec in config.email.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&ec.name, "email");
+            channels.push(Box::new(email::EmailChannel::new(ec, template)?));
+        }
 
-        if let Some(ref dc) = config.discord {
-            if dc.enabled {
-                channels.push(Box::new(discord::DiscordChannel::new(dc)?));
-            }
+        for wc in config.webhook.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&wc.name, "webhook");
+            channels.push(Box::new(webhook::WebhookChannel::new(wc, template, templates.body_for(&wc.name))?));
         }
 
-        if let Some(ref sc) = config.slack {
-            if sc.enabled {
-                channels.push(Box::new(slack::SlackChannel::new(sc)?));
-            }
+        for sc in config.syslog.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&sc.name, "syslog");
+            channels.push(Box::new(syslog::SyslogChannel::new(sc, template)?));
         }
 
-        if let Some(ref wc) = config.webhook {
-            if wc.enabled {
-                channels.push(Box::new(webhook::WebhookChannel::new(wc)?));
-            }
+        for pc in config.pagerduty.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&pc.name, "pagerduty");
+            channels.push(Box::new(pagerduty::PagerDutyChannel::new(pc, template, templates.runbooks())?));
         }
 
-        // TODO: Add Telegram, Email, Syslog channels
+        for tc in config.teams.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&tc.name, "teams");
+            channels.push(Box::new(teams::TeamsChannel::new(tc, template)?));
+        }
+
+        for mc in config.mattermost.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&mc.name, "mattermost");
+            channels.push(Box::new(mattermost::MattermostChannel::new(mc, template)?));
+        }
+
+        for gc in config.google_chat.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&gc.name, "google_chat");
+            channels.push(Box::new(google_chat::GoogleChatChannel::new(gc, template)?));
+        }
+
+        for nc in config.ntfy.iter().filter(|c| c.enabled) {
+            let template = templates.for_channel(&nc.name, "ntfy");
+            channels.push(Box::new(ntfy::NtfyChannel::new(nc, template, templates.runbooks())?));
+        }
 
         tracing::info!(channels = channels.len(), "Initialized alert channels");
 
-        Ok(Self {
+        let router = routing::Router::new(config.route.as_ref(), &names)?;
+        let journal = Arc::new(journal::Journal::new(config, data_dir)?);
+        let files = persist::FileWriter::spawn()?;
+        let limiters = channels.iter().map(|_| rate_limit::ChannelLimiter::new(config)).collect();
+        let channels: Vec<Arc<dyn AlertChannel>> = channels.into_iter().map(Arc::from).collect();
+        let (outboxes, workers): (Vec<_>, Vec<_>) = channels.iter()
+            .map(|c| delivery::Outbox::new(c.clone(), config, data_dir, journal.clone(), files.clone()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        for (worker, fallback) in workers.into_iter().zip(channel_fallbacks(config, &names)?) {
+            worker.start(fallback.map(|i| outboxes[i].clone()));
+        }
+
+        let mut manager = Self {
             channels,
+            outboxes,
             dedup_map: HashMap::new(),
             dedup_window_secs: config.dedup_window_secs,
-            rate_limiter: RateLimiter::new(config.rate_limit_per_minute),
-        })
+            repeat_interval_secs: config.repeat_interval_secs,
+            last_cleanup: Instant::now(),
+            limiters,
+            active: active::ActiveAlerts::new(config.recovery_clear_secs),
+            recovery_enabled: config.recovery_enabled,
+            escalator: escalation::Escalator::new(&config.escalation)?,
+            grouper: (config.group_window_secs > 0)
+                .then(|| grouping::AlertGrouper::new(config.group_window_secs, &config.group_by)),
+            silences: silence::Silences::load(&config.silences, data_dir)?,
+            router,
+            journal,
+            state_file: state::StateFile::new(data_dir, files.clone()),
+            files,
+            state_dirty: false,
+        };
+        manager.restore_state();
+        Ok(manager)
     }
 
-    pub async fn dispatch(&mut self, alert: Alert) -> Result<()> {
-        // Check deduplication
-        let key = DeduplicationKey {
-            metric: alert.metric,
-            severity: alert.severity,
+    /// Pick up the conditions and notification times saved before a restart,
+    /// so still-firing alerts are not sent again and ones that cleared while
+    /// the agent was down are resolved
+    fn restore_state(&mut self) {
+        let saved = self.state_file.load();
+        let tracked: HashSet<active::ActiveKey> = saved.active.iter().map(|a| active::ActiveKey::of(&a.alert)).collect();
+        let notified: HashSet<active::ActiveKey> = saved.active.iter()
+            .filter(|a| a.notified)
+            .map(|a| active::ActiveKey::of(&a.alert))
+            .collect();
+
+        let now = chrono::Utc::now();
+        let dedup_window = chrono::Duration::seconds(self.dedup_window_secs as i64);
+        for entry in saved.dedup {
+            let last_seen = if notified.contains(&entry.key.condition) {
+                // Still tracked: the downtime does not count as not seen
+                now
+            } else if tracked.contains(&entry.key.condition) || now - entry.last_seen >= dedup_window {
+                // Never notified (e.g. held in a group at shutdown) or expired
+                continue;
+            } else {
+                entry.last_seen
+            };
+            // Notified before a reboot: the repeat interval counts as over
+            let (Some(last_sent), Some(last_seen)) = (state::to_instant(entry.last_sent), state::to_instant(last_seen)) else {
+                continue;
+            };
+            self.dedup_map.insert(entry.key, DedupEntry {
+                last_sent: last_sent.into(),
+                last_seen: last_seen.into(),
+                count: entry.count,
+            });
+        }
+
+        for limiter in saved.limiters {
+            // Budgets of channels since removed from the config are dropped
+            if let Some(i) = self.channels.iter().position(|c| c.name() == limiter.channel) {
+                self.limiters[i].restore(limiter);
+            }
+        }
+
+        if !saved.active.is_empty() || !self.dedup_map.is_empty() {
+            tracing::info!(
+                active = saved.active.len(),
+                dedup = self.dedup_map.len(),
+                saved_at = ?saved.saved_at,
+                "Restored alert state"
+            );
+        }
+        self.active.restore(saved.active);
+    }
+
+    fn save_state(&mut self) {
+        let state = state::SavedState {
+            saved_at: Some(chrono::Utc::now()),
+            active: self.active.entries().cloned().collect(),
+            dedup: self.dedup_map.iter()
+                .map(|(key, e)| state::SavedDedup {
+                    key: key.clone(),
+                    last_sent: state::to_wall_clock(e.last_sent.into_std()),
+                    last_seen: state::to_wall_clock(e.last_seen.into_std()),
+                    count: e.count,
+                })
+                .collect(),
+            limiters: self.channels.iter()
+                .zip(&self.limiters)
+                .map(|(channel, limiter)| limiter.save(channel.name()))
+                .collect(),
         };
+        self.state_file.save(&state);
+        self.state_dirty = false;
+    }
 
-        if let Some(entry) = self.dedup_map.get_mut(&key) {
-            let elapsed = entry.last_sent.elapsed().as_secs();
-            if elapsed < self.dedup_window_secs && alert.severity < Severity::Emergency {
-                entry.count += 1;
-                tracing::debug!(metric = %alert.metric, count = entry.count, "Alert deduplicated");
-                return Ok(());
+    /// Save the state before the agent exits, including counters of
+    /// deduplicated alerts that are otherwise only saved periodically, and
+    /// write out the queued journal entries and dead letters
+    pub async fn shutdown(&mut self) {
+        self.save_state();
+        let journal = self.journal.clone();
+        let files = self.files.clone();
+        let flushed = tokio::task::spawn_blocking(move || {
+            journal.flush();
+            files.flush();
+        }).await;
+        if let Err(e) = flushed {
+            tracing::error!(error = %e, "Failed to flush alert files on shutdown");
+        }
+    }
+
+    /// Delivery queues of the channels, in channel order, for health reporting
+    pub fn outboxes(&self) -> &[delivery::Outbox] {
+        &self.outboxes
+    }
+
+    /// ID of the active silence matching an alert as raised by its analyzer
+    pub fn silenced_by(&self, alert: &Alert) -> Option<&str> {
+        self.silences.silenced_by(alert)
+    }
+
+    pub fn dispatch(&mut self, mut alert: Alert) -> Result<()> {
+        let silenced = self.silences.silenced_by(&alert).map(str::to_owned);
+
+        // Track the condition for escalation and recovery before any suppression
+        if alert.source != crate::log_analyzer::SOURCE {
+            let state = self.active.observe(&mut alert);
+            let (new, previous) = (state.occurrences == 1, state.alert.severity);
+            self.escalator.apply(&mut alert, state);
+            self.active.update(&alert);
+            // Repeats of a known condition only move counters
+            if new || alert.severity != previous {
+                self.state_dirty = true;
             }
         }
 
-        // Check rate limit (bypass for Emergency)
-        if alert.severity < Severity::Emergency && !self.rate_limiter.try_acquire() {
-            tracing::warn!(metric = %alert.metric, "Alert rate limited");
+        if let Some(id) = silenced {
+            tracing::debug!(metric = %alert.metric, silence = %id, "Alert silenced");
+            self.journal.record_detail(&alert, journal::Outcome::Silenced, &id);
+            // Someone is handling it: stop incident escalation where it was paged
+            if self.active.acknowledge(&alert) {
+                self.state_dirty = true;
+                self.update_incidents(alert, delivery::Event::Acknowledge);
+            }
             return Ok(());
         }
 
-        // Dispatch to all matching channels
-        for channel in &self.channels {
-            if channel.accepts_severity(&alert.severity) {
-                if let Err(e) = channel.send(&alert).await {
-                    tracing::error!(
-                        channel = channel.name(),
-                        error = %e,
-                        "Failed to send alert"
+        // Deduplicate (bypass for Emergency raised as such). An entry lives
+        // while the condition keeps recurring within the dedup window; during
+        // that time the alert is repeated once per repeat interval with its
+        // count. Severity is part of the key, so an escalation notifies once
+        // and is then repeated like any other alert.
+        let key = DeduplicationKey::of(&alert);
+        let now = Instant::now();
+        let dedup_window = Duration::from_secs(self.dedup_window_secs);
+        match self.dedup_map.get_mut(&key) {
+            Some(entry) if now.duration_since(entry.last_seen) < dedup_window => {
+                entry.count += 1;
+                entry.last_seen = now;
+                let since_sent = now.duration_since(entry.last_sent);
+                if since_sent < Duration::from_secs(self.repeat_interval_secs)
+                    && (alert.severity < Severity::Emergency || alert.escalated_from.is_some())
+                {
+                    tracing::debug!(metric = %alert.metric, count = entry.count, "Alert deduplicated");
+                    self.journal.record_detail(
+                        &alert,
+                        journal::Outcome::Deduplicated,
+                        format!("occurrence {} since last notification", entry.count),
+                    );
+                    return Ok(());
+                }
+                if entry.count > 1 {
+                    let window = chrono::Duration::from_std(since_sent).unwrap_or_default();
+                    alert.message = format!(
+                        "{} (occurred {} times in last {})",
+                        alert.message,
+                        entry.count,
+                        format_duration(window)
                     );
+                    tracing::info!(metric = %alert.metric, count = entry.count, "Repeating alert");
                 }
+                entry.last_sent = now;
+                entry.count = 0;
+            }
+            _ => {
+                self.dedup_map.insert(key, DedupEntry {
+                    last_sent: now,
+                    last_seen: now,
+                    count: 0,
+                });
+            }
+        }
+
+        self.state_dirty = true;
+        self.journal.record(&alert, journal::Outcome::Fired);
+        self.enqueue(alert);
+        Ok(())
+    }
+
+    /// Periodic work: send resolutions and flush groups whose window elapsed
+    pub fn tick(&mut self) -> Result<()> {
+        self.process_recoveries()?;
+
+        if self.last_cleanup.elapsed() >= CLEANUP_INTERVAL {
+            let dedup_window = Duration::from_secs(self.dedup_window_secs);
+            let before = self.dedup_map.len();
+            self.dedup_map.retain(|_, e| e.last_seen.elapsed() < dedup_window);
+            self.silences.prune_expired();
+            self.last_cleanup = Instant::now();
+            // Also checkpoints the counters of alerts still being deduplicated
+            if !self.dedup_map.is_empty() || self.dedup_map.len() != before {
+                self.state_dirty = true;
             }
         }
 
-        // Update dedup map
-        self.dedup_map.insert(key, DedupEntry {
-            last_sent: Instant::now(),
-            count: 1,
-        });
+        let due = match self.grouper {
+            Some(ref mut grouper) => grouper.take_due(),
+            None => Vec::new(),
+        };
+        for group in due {
+            self.deliver(&group);
+        }
+
+        // Report suppressed alerts on the channel that suppressed them
+        for ((channel, outbox), limiter) in self.channels.iter().zip(&self.outboxes).zip(self.limiters.iter_mut()) {
+            if let Some(summary) = limiter.take_summary() {
+                self.state_dirty = true;
+                tracing::info!(channel = channel.name(), summary = %summary.message, "Sending rate limit summary");
+                outbox.submit(vec![summary]);
+            }
+        }
+
+        // At most one write per tick, however many alerts came in
+        if self.state_dirty {
+            self.save_state();
+        }
+        Ok(())
+    }
+
+    /// Hold the alert for its group, or deliver it right away when grouping
+    /// is disabled. Emergency alerts are never delayed.
+    fn enqueue(&mut self, alert: Alert) {
+        match self.grouper {
+            Some(ref mut grouper) if alert.severity < Severity::Emergency => grouper.add(alert),
+            _ => self.deliver(std::slice::from_ref(&alert)),
+        }
+    }
+
+    /// Send RESOLVED notifications for conditions that have cleared.
+    /// Resolutions bypass dedup and rate limiting; each one is sent once.
+    fn process_recoveries(&mut self) -> Result<()> {
+        let entries = self.active.take_cleared();
+        if !entries.is_empty() {
+            self.state_dirty = true;
+        }
+        for entry in entries {
+            // A new occurrence after recovery should notify immediately,
+            // whatever severity it was escalated through
+            let cleared = active::ActiveKey::of(&entry.alert);
+            self.dedup_map.retain(|k, _| k.condition != cleared);
+
+            // Escalation state goes with the entry, so a recurrence starts over
+            if !self.recovery_enabled || !entry.notified {
+                continue;
+            }
+            let silenced = self.silences.silenced_by(&entry.alert).map(str::to_owned);
+            let mut alert = entry.alert;
+            alert.status = AlertStatus::Resolved;
+            alert.timestamp = chrono::Utc::now();
+            alert.started_at = Some(entry.first_seen);
+
+            if let Some(id) = silenced {
+                tracing::debug!(metric = %alert.metric, silence = %id, "Resolution silenced");
+                self.journal.record_detail(&alert, journal::Outcome::Silenced, &id);
+                // An acknowledged incident must still be closed
+                if entry.acknowledged {
+                    self.update_incidents(alert, delivery::Event::Notify);
+                }
+                continue;
+            }
+
+            tracing::info!(
+                metric = %alert.metric,
+                source = %alert.source,
+                duration = %alert.active_duration().map(format_duration).unwrap_or_default(),
+                "Alert resolved"
+            );
+            self.journal.record(&alert, journal::Outcome::Resolved);
+            self.enqueue(alert);
+        }
 
         Ok(())
     }
+
+    /// Answer a control request. Journal queries read files, so they are
+    /// answered from a blocking task rather than holding up dispatch.
+    pub fn handle_control(&mut self, msg: ControlMessage) {
+        let ControlMessage { request, reply } = msg;
+        let result = match request {
+            ControlRequest::AddSilence { silence } => self.silences.add(silence)
+                .map(|id| ControlResponse { id: Some(id), ..Default::default() }),
+            ControlRequest::RemoveSilence { id } => self.silences.remove(&id)
+                .map(|()| ControlResponse::default()),
+            ControlRequest::ListSilences => Ok(ControlResponse {
+                silences: Some(self.silences.list()),
+                ..Default::default()
+            }),
+            ControlRequest::QueryJournal { query } => {
+                let journal = self.journal.clone();
+                tokio::spawn(async move {
+                    let result = tokio::task::spawn_blocking(move || journal.query(&query)).await
+                        .map_err(anyhow::Error::from)
+                        .and_then(|r| r)
+                        .map(|entries| ControlResponse { entries: Some(entries), ..Default::default() });
+                    let _ = reply.send(ControlResponse::from_result(result));
+                });
+                return;
+            }
+        };
+        let _ = reply.send(ControlResponse::from_result(result));
+    }
+
+    /// Acknowledge or resolve a silenced condition's incident on the routed
+    /// channels that track incidents. Not rate limited, like resolutions.
+    fn update_incidents(&self, alert: Alert, event: delivery::Event) {
+        for i in self.router.route(&alert) {
+            let channel = &self.channels[i];
+            if !channel.acknowledges() || !channel.accepts_severity(&alert.severity) {
+                continue;
+            }
+            tracing::info!(channel = channel.name(), metric = %alert.metric, event = ?event, "Updating incident of silenced alert");
+            match event {
+                delivery::Event::Acknowledge => self.outboxes[i].acknowledge(alert.clone()),
+                delivery::Event::Notify => self.outboxes[i].submit(vec![alert.clone()]),
+            }
+        }
+    }
+
+    /// Queue alerts for every channel they are routed to that accepts their
+    /// severity and is within its rate limits; a channel that takes more than
+    /// one gets them as a single grouped notification
+    fn deliver(&mut self, alerts: &[Alert]) {
+        // Rate limit budgets and notified flags change
+        self.state_dirty = true;
+        let mut routed = vec![false; alerts.len()];
+        let mut notified = vec![false; alerts.len()];
+        let routes: Vec<Vec<usize>> = alerts.iter().map(|a| self.router.route(a)).collect();
+        let channels = self.channels.iter().zip(&self.outboxes).zip(self.limiters.iter_mut());
+        for (i, ((channel, outbox), limiter)) in channels.enumerate() {
+            let accepted: Vec<&Alert> = alerts.iter()
+                .zip(&routes)
+                .filter(|(a, r)| r.contains(&i) && channel.accepts_severity(&a.severity))
+                .map(|(a, _)| a)
+                .collect();
+            if accepted.is_empty() {
+                continue;
+            }
+            let admitted = limiter.admit(accepted.clone());
+            for alert in &accepted {
+                let Some(i) = alerts.iter().position(|a| std::ptr::eq(a, *alert)) else { continue };
+                routed[i] = true;
+                if admitted.iter().any(|a| std::ptr::eq(*a, *alert)) {
+                    notified[i] = true;
+                } else {
+                    self.journal.record_delivery(channel.name(), std::slice::from_ref(*alert), journal::Outcome::RateLimited, None);
+                }
+            }
+            if !admitted.is_empty() {
+                outbox.submit(admitted.into_iter().cloned().collect());
+            }
+        }
+
+        for (alert, _) in alerts.iter().zip(routed).filter(|(_, r)| !*r) {
+            tracing::debug!(metric = %alert.metric, "No channel routed or accepting alert");
+            self.journal.record(alert, journal::Outcome::Unrouted);
+        }
+        for (alert, _) in alerts.iter().zip(notified).filter(|(_, n)| *n) {
+            self.active.mark_notified(alert);
+        }
+    }
+}
+
+/// Name and fallback of the enabled channel instances, in the order channels are created
+fn enabled_channels(config: &AlertingConfig) -> Vec<(&str, Option<&str>)> {
+    config.discord.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref()))
+        .chain(config.slack.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.telegram.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.email.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.webhook.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.syslog.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.pagerduty.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.teams.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.mattermost.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.google_chat.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .chain(config.ntfy.iter().filter(|c| c.enabled).map(|c| (c.name.as_str(), c.fallback.as_deref())))
+        .collect()
+}
+
+/// Names of the enabled channel instances, in the order channels are created
+fn channel_names(config: &AlertingConfig) -> Result<Vec<String>> {
+    let names: Vec<String> = enabled_channels(config).into_iter().map(|(name, _)| name.to_string()).collect();
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            anyhow::bail!("Duplicate alert channel name `{}`; give each instance a unique `name`", name);
+        }
+    }
+    Ok(names)
+}
+
+/// Index of each channel's fallback channel, in channel order
+fn channel_fallbacks(config: &AlertingConfig, names: &[String]) -> Result<Vec<Option<usize>>> {
+    enabled_channels(config).into_iter()
+        .map(|(name, fallback)| match fallback {
+            Some(f) if f == name => anyhow::bail!("Channel `{}` cannot be its own fallback", name),
+            Some(f) => names.iter()
+                .position(|n| n == f)
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("Fallback `{}` of channel `{}` is not an enabled channel", f, name)),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+/// Check channel names, fallbacks, templates and routes without creating any channel
+pub fn validate(config: &AlertingConfig) -> Result<()> {
+    let names = channel_names(config)?;
+    channel_fallbacks(config, &names)?;
+    template::Templates::new(config, &names)?;
+    routing::Router::new(config.route.as_ref(), &names)?;
+    silence::validate(&config.silences)?;
+    // Method, auth and TLS files are only checked when the client is built
+    for wc in config.webhook.iter().filter(|c| c.enabled) {
+        webhook::WebhookChannel::new(wc, None, None)?;
+    }
+    Ok(())
+}
+
+/// HTTP client builder for a channel; each request gives up after `timeout_secs`
+pub(crate) fn http_client_builder(timeout_secs: u64) -> reqwest::ClientBuilder {
+    reqwest::Client::builder().timeout(Duration::from_secs(timeout_secs.max(1)))
+}
+
+/// Human-readable duration for alert messages, e.g. `1h 5m 3s`
+pub fn format_duration(d: chrono::Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// `[SEVERITY]` for firing alerts, `[RESOLVED]` for recoveries
+pub fn status_tag(alert: &Alert) -> String {
+    match alert.status {
+        AlertStatus::Firing => format!("[{}]", alert.severity),
+        AlertStatus::Resolved => "[RESOLVED]".to_string(),
+    }
+}
+
+/// One-line summary of a grouped notification,
+/// e.g. `3 disk alerts on web-01: 2 CRITICAL, 1 RESOLVED`
+pub fn group_summary(alerts: &[Alert]) -> String {
+    let Some(first) = alerts.first() else { return String::new() };
+
+    let family = grouping::metric_family(first);
+    let subject = if alerts.iter().all(|a| grouping::metric_family(a) == family) {
+        format!("{} {} alerts", alerts.len(), family)
+    } else {
+        format!("{} alerts", alerts.len())
+    };
+
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    let mut sorted: Vec<&Alert> = alerts.iter().collect();
+    // Firing by descending severity, then resolutions
+    sorted.sort_by_key(|a| (a.status == AlertStatus::Resolved, std::cmp::Reverse(a.severity)));
+    for alert in sorted {
+        let tag = match alert.status {
+            AlertStatus::Firing => alert.severity.to_string(),
+            AlertStatus::Resolved => "RESOLVED".to_string(),
+        };
+        match counts.last_mut() {
+            Some((last, n)) if *last == tag => *n += 1,
+            _ => counts.push((tag, 1)),
+        }
+    }
+    let counts = counts.iter()
+        .map(|(tag, n)| format!("{} {}", n, tag))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{} on {}: {}", subject, first.hostname, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MetricId;
+
+    fn warn_alert() -> Alert {
+        Alert {
+            timestamp: chrono::Utc::now(),
+            severity: Severity::Warn,
+            metric: MetricId::CpuUsage,
+            value: 91.0,
+            threshold: Some(90.0),
+            message: "CPU usage 91.0%".into(),
+            labels: Default::default(),
+            hostname: "web-01".into(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn escalated_alert_notifies_once_per_repeat_interval() {
+        let dir = std::env::temp_dir().join(format!("sysops-agent-escalation-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap();
+        let config: AlertingConfig = toml::from_str(
+            r#"
+            repeat_interval_secs = 1
+            [[escalation]]
+            warn_to_critical_after = 3
+            critical_to_emergency_after_mins = 0
+            "#,
+        ).unwrap();
+        let mut manager = AlertManager::new(&config, data_dir).unwrap();
+
+        // Raised on every analyzer pass: Warn twice, then escalated to Emergency
+        for _ in 0..10 {
+            manager.dispatch(warn_alert()).unwrap();
+        }
+        tokio::time::advance(Duration::from_secs(1)).await;
+        manager.dispatch(warn_alert()).unwrap();
+
+        let fired = journal::JournalQuery { outcome: vec!["fired".into()], ..Default::default() };
+        let mut notified: Vec<Severity> = manager.journal.query(&fired).unwrap()
+            .into_iter()
+            .map(|e| e.severity)
+            .collect();
+        notified.reverse();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // The escalation notifies once, then repeats once the interval has passed
+        assert_eq!(notified, [Severity::Warn, Severity::Emergency, Severity::Emergency]);
+    }
+
+    #[tokio::test]
+    async fn dedup_from_before_a_reboot_counts_as_expired() {
+        let dir = std::env::temp_dir().join(format!("sysops-agent-reboot-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap();
+        let config: AlertingConfig = toml::from_str(
+            r#"
+            repeat_interval_secs = 3600
+            group_window_secs = 0
+            retry_max_attempts = 1
+            [[webhook]]
+            enabled = true
+            url = "http://127.0.0.1:9/unreachable"
+            "#,
+        ).unwrap();
+        let mut manager = AlertManager::new(&config, data_dir).unwrap();
+        manager.dispatch(warn_alert()).unwrap();
+        manager.shutdown().await;
+
+        // Notified long before the monotonic clock started
+        let path = dir.join("alert_state.json");
+        let mut saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        for entry in saved["dedup"].as_array_mut().unwrap() {
+            entry["last_sent"] = "2000-01-01T00:00:00Z".into();
+        }
+        std::fs::write(&path, saved.to_string()).unwrap();
+
+        let mut manager = AlertManager::new(&config, data_dir).unwrap();
+        manager.dispatch(warn_alert()).unwrap();
+        let fired = journal::JournalQuery { outcome: vec!["fired".into()], ..Default::default() };
+        let notified = manager.journal.query(&fired).unwrap().len();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(notified, 2);
+    }
 }
@@ -0,0 +1,145 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::config::NtfyConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::{no_escape, Runbooks, Template};
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+/// ntfy truncates larger messages into an attachment
+const MAX_MESSAGE_LEN: usize = 4096;
+
+/// ntfy push notification channel, publishing JSON messages to a topic.
+/// Notifications have no colors; severity is shown by priority and an
+/// emoji tag instead.
+pub struct NtfyChannel {
+    name: String,
+    /// Server root, where JSON messages naming their topic are published
+    server: String,
+    topic: String,
+    token: Option<String>,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    runbooks: Arc<Runbooks>,
+    client: reqwest::Client,
+}
+
+impl NtfyChannel {
+    pub fn new(config: &NtfyConfig, template: Option<Template>, runbooks: Arc<Runbooks>) -> Result<Self> {
+        if config.topic.trim().is_empty() {
+            bail!("ntfy channel `{}` needs a topic", config.name);
+        }
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name.clone(),
+            server: config.server.trim_end_matches('/').to_string(),
+            topic: config.topic.clone(),
+            token: config.token.clone().filter(|t| !t.is_empty()),
+            severity_filter,
+            template,
+            runbooks,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    fn body(&self, alert: &Alert) -> String {
+        if let Some(ref template) = self.template {
+            return template.render(alert, no_escape);
+        }
+        let mut body = format!("{}: {} = {:.2}", alert.hostname, alert.metric, alert.value);
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            body.push_str(&format!(" (active {})", format_duration(d)));
+        }
+        body
+    }
+
+    async fn publish(&self, mut message: serde_json::Value) -> Result<()> {
+        message["topic"] = json!(self.topic);
+        if let Some(text) = message["message"].as_str().filter(|t| t.len() > MAX_MESSAGE_LEN) {
+            let mut end = MAX_MESSAGE_LEN;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            message["message"] = json!(&text[..end]);
+        }
+
+        let mut req = self.client.post(&self.server).json(&message);
+        if let Some(ref token) = self.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await?;
+        check_status(resp)?;
+        Ok(())
+    }
+
+    /// ntfy priority (1 min to 5 max) and emoji tag
+    fn severity_style(alert: &Alert) -> (u8, &'static str) {
+        match (alert.status, alert.severity) {
+            (AlertStatus::Resolved, _) => (2, "white_check_mark"),
+            (AlertStatus::Firing, Severity::Info) => (2, "information_source"),
+            (AlertStatus::Firing, Severity::Warn) => (3, "warning"),
+            (AlertStatus::Firing, Severity::Critical) => (4, "rotating_light"),
+            (AlertStatus::Firing, Severity::Emergency) => (5, "sos"),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for NtfyChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let (priority, tag) = Self::severity_style(alert);
+        let mut message = json!({
+            "title": format!("{} {}", status_tag(alert), alert.message),
+            "message": self.body(alert),
+            "priority": priority,
+            "tags": [tag, &alert.hostname],
+        });
+        if let Some(url) = self.runbooks.url_for(alert) {
+            message["actions"] = json!([{ "action": "view", "label": "Runbook", "url": url }]);
+        }
+        self.publish(message).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        // The most urgent alert decides how the notification is delivered
+        let (priority, tag) = alerts.iter()
+            .map(Self::severity_style)
+            .max_by_key(|(priority, _)| *priority)
+            .unwrap_or((3, "warning"));
+        let lines: Vec<String> = alerts.iter()
+            .map(|a| match self.template {
+                Some(ref template) => template.render(a, no_escape),
+                None => format!("{} {}", status_tag(a), a.message),
+            })
+            .collect();
+        let message = json!({
+            "title": group_summary(alerts),
+            "message": lines.join("\n"),
+            "priority": priority,
+            "tags": [tag],
+        });
+        self.publish(message).await
+    }
+}
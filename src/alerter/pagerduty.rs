@@ -0,0 +1,174 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::config::PagerDutyConfig;
+use crate::storage::SeriesKey;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::grouping::metric_family;
+use super::template::{no_escape, Runbooks, Template};
+use super::{http_client_builder, AlertChannel};
+
+/// Events API limits
+const MAX_SUMMARY_LEN: usize = 1024;
+const MAX_DEDUP_KEY_LEN: usize = 255;
+
+/// PagerDuty Events API v2 alert channel. Firing alerts trigger an incident,
+/// recoveries resolve it and silences acknowledge it, all correlated by a
+/// dedup key stable across restarts.
+pub struct PagerDutyChannel {
+    name: String,
+    /// Sent in every event body; never logged
+    routing_key: String,
+    events_url: String,
+    client_url: Option<String>,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    runbooks: Arc<Runbooks>,
+    client: reqwest::Client,
+}
+
+impl PagerDutyChannel {
+    pub fn new(config: &PagerDutyConfig, template: Option<Template>, runbooks: Arc<Runbooks>) -> Result<Self> {
+        if config.routing_key.trim().is_empty() {
+            bail!("PagerDuty channel `{}` needs a routing_key", config.name);
+        }
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name.clone(),
+            routing_key: config.routing_key.clone(),
+            events_url: config.events_url.clone(),
+            client_url: config.client_url.clone(),
+            severity_filter,
+            template,
+            runbooks,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    fn trigger(&self, alert: &Alert) -> serde_json::Value {
+        let summary = match self.template {
+            Some(ref template) => template.render(alert, no_escape),
+            None => format!("{}: {}", alert.hostname, alert.message),
+        };
+        let labels: serde_json::Map<String, serde_json::Value> = alert.labels.iter()
+            .map(|l| (l.key.clone(), json!(l.value)))
+            .collect();
+        let mut details = json!({
+            "metric": alert.metric.to_string(),
+            "value": alert.value,
+            "threshold": alert.threshold,
+            "analyzer": &alert.source,
+            "message": &alert.message,
+            "labels": labels,
+        });
+        if let Some(started) = alert.started_at {
+            details["started_at"] = json!(started.to_rfc3339());
+        }
+        if let Some(original) = alert.escalated_from {
+            details["escalated_from"] = json!(original.to_string());
+        }
+
+        let mut event = json!({
+            "routing_key": &self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": dedup_key(alert),
+            "client": "SysOps Agent",
+            "payload": {
+                "summary": summary.chars().take(MAX_SUMMARY_LEN).collect::<String>(),
+                "source": &alert.hostname,
+                "severity": pd_severity(alert.severity),
+                "timestamp": alert.timestamp.to_rfc3339(),
+                "component": alert.metric.to_string(),
+                "group": metric_family(alert),
+                "class": &alert.source,
+                "custom_details": details,
+            },
+        });
+        if let Some(ref url) = self.client_url {
+            event["client_url"] = json!(url);
+        }
+        if let Some(url) = self.runbooks.url_for(alert) {
+            event["links"] = json!([{ "href": url, "text": "Runbook" }]);
+        }
+        event
+    }
+
+    /// Acknowledge or resolve need only the key of the incident
+    fn update(&self, action: &str, alert: &Alert) -> serde_json::Value {
+        json!({
+            "routing_key": &self.routing_key,
+            "event_action": action,
+            "dedup_key": dedup_key(alert),
+        })
+    }
+
+    async fn post(&self, event: &serde_json::Value) -> Result<()> {
+        let resp = self.client.post(&self.events_url)
+            .json(event)
+            .send()
+            .await?;
+        check_status(resp)?;
+        Ok(())
+    }
+}
+
+/// Info → info, Warn → warning, Critical → error, Emergency → critical
+fn pd_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warn => "warning",
+        Severity::Critical => "error",
+        Severity::Emergency => "critical",
+    }
+}
+
+/// Incident key for a condition: host, analyzer and series, e.g.
+/// `web-01/threshold/disk.usage_percent{mountpoint="/var"}`. The analyzer is
+/// part of it so a trend prediction resolving doesn't close the threshold
+/// incident for the same disk.
+fn dedup_key(alert: &Alert) -> String {
+    let key = format!("{}/{}/{}", alert.hostname, alert.source, SeriesKey::new(alert.metric, &alert.labels));
+    if key.len() <= MAX_DEDUP_KEY_LEN {
+        return key;
+    }
+    // FNV-1a: stable across builds and restarts, unlike the std hashers
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    format!("sysops/{:016x}", hash)
+}
+
+#[async_trait]
+impl AlertChannel for PagerDutyChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let event = match alert.status {
+            AlertStatus::Firing => self.trigger(alert),
+            AlertStatus::Resolved => self.update("resolve", alert),
+        };
+        self.post(&event).await
+    }
+
+    fn acknowledges(&self) -> bool {
+        true
+    }
+
+    async fn acknowledge(&self, alert: &Alert) -> Result<()> {
+        self.post(&self.update("acknowledge", alert)).await
+    }
+}
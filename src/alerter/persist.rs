@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+/// How long a flush waits for queued saves to be written
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Saves the alert manager's files in `data_dir` (alert state, dead letters)
+/// on a background thread, so persisting never blocks an async task. Files
+/// are written atomically (temp file + rename); when several saves of one
+/// file are queued, only the latest is written.
+#[derive(Clone)]
+pub struct FileWriter {
+    tx: Sender<WriterMsg>,
+}
+
+/// Messages to the writer thread
+enum WriterMsg {
+    /// New contents of a file, or `None` to remove it. `what` names the
+    /// file in error messages.
+    Save { path: PathBuf, contents: Option<Vec<u8>>, what: &'static str },
+    /// Acknowledge once everything queued before has been written
+    Flush(Sender<()>),
+}
+
+impl FileWriter {
+    pub fn spawn() -> Result<Self> {
+        let (tx, rx) = channel();
+        std::thread::Builder::new()
+            .name("file-writer".into())
+            .spawn(move || run(rx))?;
+        Ok(Self { tx })
+    }
+
+    /// Queue `contents` to replace the file at `path`
+    pub fn save(&self, path: &Path, contents: Vec<u8>, what: &'static str) {
+        self.send(path, Some(contents), what);
+    }
+
+    /// Queue the removal of the file at `path`
+    pub fn remove(&self, path: &Path, what: &'static str) {
+        self.send(path, None, what);
+    }
+
+    fn send(&self, path: &Path, contents: Option<Vec<u8>>, what: &'static str) {
+        let msg = WriterMsg::Save { path: path.to_path_buf(), contents, what };
+        if self.tx.send(msg).is_err() {
+            tracing::error!(path = %path.display(), "File writer has stopped, {} not saved", what);
+        }
+    }
+
+    /// Wait until the saves queued so far are written.
+    /// Blocks, so call it off the async runtime.
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = channel();
+        if self.tx.send(WriterMsg::Flush(done_tx)).is_err() {
+            return;
+        }
+        if done_rx.recv_timeout(FLUSH_TIMEOUT).is_err() {
+            tracing::warn!("Timed out saving alert files");
+        }
+    }
+}
+
+fn run(rx: Receiver<WriterMsg>) {
+    while let Ok(first) = rx.recv() {
+        // Everything queued meanwhile; a file saved again later in the batch is written once
+        let batch: Vec<WriterMsg> = std::iter::once(first).chain(rx.try_iter()).collect();
+        let mut acks = Vec::new();
+        for (i, msg) in batch.iter().enumerate() {
+            match msg {
+                WriterMsg::Save { path, contents, what } => {
+                    let superseded = batch[i + 1..].iter()
+                        .any(|later| matches!(later, WriterMsg::Save { path: p, .. } if p == path));
+                    if superseded {
+                        continue;
+                    }
+                    if let Err(e) = write(path, contents.as_deref()) {
+                        tracing::error!(error = %e, path = %path.display(), "Failed to persist {}", what);
+                    }
+                }
+                WriterMsg::Flush(done) => acks.push(done),
+            }
+        }
+        for done in acks {
+            let _ = done.send(());
+        }
+    }
+}
+
+fn write(path: &Path, contents: Option<&[u8]>) -> Result<()> {
+    let Some(contents) = contents else {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use smallvec::smallvec;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::config::AlertingConfig;
+use crate::types::{Alert, AlertStatus, MetricId, Severity};
+use super::state::{to_instant, to_wall_clock};
+
+/// How often a channel reports what it suppressed
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(3600);
+/// Metrics named in the suppressed summary
+const SUMMARY_TOP_N: usize = 3;
+
+/// `Alert::source` of suppressed-alert summaries
+pub const SOURCE: &str = "rate_limiter";
+
+/// Token bucket refilling `capacity` tokens per `period`; zero capacity admits nothing
+struct TokenBucket {
+    tokens: f64,
+    max_tokens: f64,
+    refill_rate: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: u32, period: Duration) -> Self {
+        Self {
+            tokens: capacity as f64,
+            max_tokens: capacity as f64,
+            refill_rate: capacity as f64 / period.as_secs_f64(),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.max_tokens);
+        self.last_refill = now;
+    }
+
+    fn available(&mut self) -> bool {
+        self.refill();
+        self.tokens >= 1.0
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        if !self.available() {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn save(&self) -> SavedBucket {
+        SavedBucket { tokens: self.tokens, last_refill: to_wall_clock(self.last_refill) }
+    }
+
+    /// Take over a saved budget, refilled for the time since it was saved;
+    /// a lowered capacity applies right away
+    fn restore(&mut self, saved: &SavedBucket) {
+        let elapsed = (Utc::now() - saved.last_refill).to_std().unwrap_or_default();
+        self.tokens = (saved.tokens + elapsed.as_secs_f64() * self.refill_rate).clamp(0.0, self.max_tokens);
+        self.last_refill = Instant::now();
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedBucket {
+    tokens: f64,
+    last_refill: DateTime<Utc>,
+}
+
+/// A channel's remaining budgets and what it suppressed, so a restart
+/// neither resets the limits nor loses the pending summary
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedLimiter {
+    pub channel: String,
+    per_minute: SavedBucket,
+    per_hour: SavedBucket,
+    #[serde(default)]
+    per_metric: Vec<(MetricId, SavedBucket)>,
+    #[serde(default)]
+    suppressed: Vec<(MetricId, u32)>,
+    worst_suppressed: Option<Severity>,
+    #[serde(default)]
+    hostname: String,
+    window_start: DateTime<Utc>,
+}
+
+/// Rate limits for one channel: a per-minute and a per-hour budget of
+/// notifications, plus a per-metric hourly budget so one noisy metric cannot
+/// use up the channel's allowance. Emergency alerts and resolutions are exempt.
+pub struct ChannelLimiter {
+    per_minute: TokenBucket,
+    per_hour: TokenBucket,
+    per_metric: HashMap<MetricId, TokenBucket>,
+    per_metric_capacity: u32,
+    suppressed: HashMap<MetricId, u32>,
+    worst_suppressed: Option<Severity>,
+    hostname: String,
+    /// Start of the summary interval; `None` once it is over
+    window_start: Option<Instant>,
+}
+
+impl ChannelLimiter {
+    pub fn new(config: &AlertingConfig) -> Self {
+        Self {
+            per_minute: TokenBucket::new(config.rate_limit_per_minute, Duration::from_secs(60)),
+            per_hour: TokenBucket::new(config.rate_limit_per_hour, Duration::from_secs(3600)),
+            per_metric: HashMap::new(),
+            per_metric_capacity: config.rate_limit_per_metric_per_hour,
+            suppressed: HashMap::new(),
+            worst_suppressed: None,
+            hostname: String::new(),
+            window_start: Some(Instant::now()),
+        }
+    }
+
+    /// Return the alerts the channel may send now; the rest are counted as
+    /// suppressed. A grouped notification costs one channel token.
+    pub fn admit<'a>(&mut self, alerts: Vec<&'a Alert>) -> Vec<&'a Alert> {
+        let (exempt, limited): (Vec<&Alert>, Vec<&Alert>) = alerts.into_iter()
+            .partition(|a| a.severity == Severity::Emergency || a.status == AlertStatus::Resolved);
+
+        let mut admitted = exempt;
+        if limited.is_empty() {
+            return admitted;
+        }
+
+        let channel_open = self.per_minute.available() && self.per_hour.available();
+        let mut sent_limited = false;
+        for alert in limited {
+            let capacity = self.per_metric_capacity;
+            let metric_ok = channel_open && self.per_metric
+                .entry(alert.metric)
+                .or_insert_with(|| TokenBucket::new(capacity, Duration::from_secs(3600)))
+                .try_acquire();
+            if metric_ok {
+                admitted.push(alert);
+                sent_limited = true;
+            } else {
+                self.record_suppressed(alert);
+            }
+        }
+
+        if sent_limited {
+            self.per_minute.try_acquire();
+            self.per_hour.try_acquire();
+        }
+        admitted
+    }
+
+    pub fn save(&self, channel: &str) -> SavedLimiter {
+        SavedLimiter {
+            channel: channel.to_string(),
+            per_minute: self.per_minute.save(),
+            per_hour: self.per_hour.save(),
+            per_metric: self.per_metric.iter().map(|(m, b)| (*m, b.save())).collect(),
+            suppressed: self.suppressed.iter().map(|(m, n)| (*m, *n)).collect(),
+            worst_suppressed: self.worst_suppressed,
+            hostname: self.hostname.clone(),
+            window_start: match self.window_start {
+                Some(start) => to_wall_clock(start),
+                None => Utc::now() - chrono::Duration::from_std(SUMMARY_INTERVAL).unwrap_or_default(),
+            },
+        }
+    }
+
+    pub fn restore(&mut self, saved: SavedLimiter) {
+        self.per_minute.restore(&saved.per_minute);
+        self.per_hour.restore(&saved.per_hour);
+        let capacity = self.per_metric_capacity;
+        for (metric, bucket) in &saved.per_metric {
+            self.per_metric.entry(*metric)
+                .or_insert_with(|| TokenBucket::new(capacity, Duration::from_secs(3600)))
+                .restore(bucket);
+        }
+        self.suppressed.extend(saved.suppressed);
+        self.worst_suppressed = saved.worst_suppressed;
+        self.hostname = saved.hostname;
+        self.window_start = to_instant(saved.window_start);
+    }
+
+    fn record_suppressed(&mut self, alert: &Alert) {
+        tracing::debug!(metric = %alert.metric, severity = %alert.severity, "Alert rate limited");
+        *self.suppressed.entry(alert.metric).or_insert(0) += 1;
+        self.worst_suppressed = self.worst_suppressed.max(Some(alert.severity));
+        self.hostname.clone_from(&alert.hostname);
+    }
+
+    /// Once per interval, summarize what was suppressed as an alert to send
+    /// on this channel, e.g. "37 alerts suppressed in last hour, top: disk.usage_percent (30)"
+    pub fn take_summary(&mut self) -> Option<Alert> {
+        if self.window_start.is_some_and(|start| start.elapsed() < SUMMARY_INTERVAL) {
+            return None;
+        }
+        self.window_start = Some(Instant::now());
+
+        let severity = self.worst_suppressed.take()?;
+        let mut counts: Vec<(MetricId, u32)> = self.suppressed.drain().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_string().cmp(&b.0.to_string())));
+        let total: u32 = counts.iter().map(|(_, n)| n).sum();
+        let top = counts.iter()
+            .take(SUMMARY_TOP_N)
+            .map(|(metric, n)| format!("{} ({})", metric, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        Some(Alert {
+            timestamp: Utc::now(),
+            severity,
+            metric: counts[0].0,
+            value: total as f64,
+            threshold: None,
+            message: format!("{} alerts suppressed in last hour, top: {}", total, top),
+            labels: smallvec![],
+            hostname: self.hostname.clone(),
+            source: SOURCE.into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(metric: MetricId, severity: Severity) -> Alert {
+        Alert {
+            timestamp: Utc::now(),
+            severity,
+            metric,
+            value: 1.0,
+            threshold: None,
+            message: String::new(),
+            labels: smallvec![],
+            hostname: "web-01".into(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    #[test]
+    fn zero_budget_suppresses_all_but_emergency() {
+        let config = AlertingConfig { rate_limit_per_minute: 0, ..Default::default() };
+        let mut limiter = ChannelLimiter::new(&config);
+        let warn = alert(MetricId::CpuUsage, Severity::Warn);
+        let emergency = alert(MetricId::CpuUsage, Severity::Emergency);
+
+        let admitted = limiter.admit(vec![&warn, &emergency]);
+
+        assert_eq!(admitted.len(), 1);
+        assert_eq!(admitted[0].severity, Severity::Emergency);
+    }
+
+    #[test]
+    fn per_metric_budget_leaves_room_for_other_metrics() {
+        let config = AlertingConfig { rate_limit_per_metric_per_hour: 1, ..Default::default() };
+        let mut limiter = ChannelLimiter::new(&config);
+        let (cpu, mem) = (alert(MetricId::CpuUsage, Severity::Warn), alert(MetricId::MemUsage, Severity::Warn));
+
+        assert_eq!(limiter.admit(vec![&cpu]).len(), 1);
+        assert!(limiter.admit(vec![&cpu]).is_empty());
+        assert_eq!(limiter.admit(vec![&mem]).len(), 1);
+    }
+}
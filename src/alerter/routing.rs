@@ -0,0 +1,128 @@
+use anyhow::{bail, Context, Result};
+
+use crate::config::RouteConfig;
+use crate::types::{Alert, Label, MetricId, Severity};
+
+/// A node of the routing tree with matchers parsed and receivers resolved
+/// to channel indices
+struct Route {
+    severities: Vec<Severity>,
+    metric: Option<String>,
+    labels: Vec<Label>,
+    source: Option<String>,
+    receivers: Vec<usize>,
+    continue_matching: bool,
+    routes: Vec<Route>,
+}
+
+impl Route {
+    fn build(config: &RouteConfig, inherited: &[usize], channels: &[String]) -> Result<Self> {
+        let severities = config.severity.iter()
+            .map(|s| match s.as_str() {
+                "info" => Ok(Severity::Info),
+                "warn" => Ok(Severity::Warn),
+                "critical" => Ok(Severity::Critical),
+                "emergency" => Ok(Severity::Emergency),
+                other => bail!("Unknown severity in route: {}", other),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(ref metric) = config.metric {
+            if !metric.ends_with('*') {
+                metric.parse::<MetricId>()
+                    .map_err(anyhow::Error::msg)
+                    .context("Invalid metric in route")?;
+            }
+        }
+        let receivers = if config.receivers.is_empty() {
+            inherited.to_vec()
+        } else {
+            config.receivers.iter()
+                .map(|name| channels.iter()
+                    .position(|c| c == name)
+                    .with_context(|| format!("Route receiver `{}` is not an enabled channel", name)))
+                .collect::<Result<Vec<_>>>()?
+        };
+        let routes = config.routes.iter()
+            .map(|child| Route::build(child, &receivers, channels))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            severities,
+            metric: config.metric.clone(),
+            labels: config.labels.iter()
+                .map(|(k, v)| Label { key: k.clone(), value: v.clone() })
+                .collect(),
+            source: config.source.clone(),
+            receivers,
+            continue_matching: config.continue_matching,
+            routes,
+        })
+    }
+
+    fn matches(&self, alert: &Alert) -> bool {
+        let metric_ok = match self.metric {
+            Some(ref pattern) => match pattern.strip_suffix('*') {
+                Some(prefix) => alert.metric.to_string().starts_with(prefix),
+                None => alert.metric.to_string() == *pattern,
+            },
+            None => true,
+        };
+        metric_ok
+            && (self.severities.is_empty() || self.severities.contains(&alert.severity))
+            && self.labels.iter().all(|want| alert.labels.iter().any(|l| l == want))
+            && self.source.as_ref().is_none_or(|s| *s == alert.source)
+    }
+
+    /// Add the receivers for `alert` if this route matches; returns whether it did
+    fn collect(&self, alert: &Alert, out: &mut Vec<usize>) -> bool {
+        if !self.matches(alert) {
+            return false;
+        }
+        let mut child_matched = false;
+        for child in &self.routes {
+            if child.collect(alert, out) {
+                child_matched = true;
+                if !child.continue_matching {
+                    break;
+                }
+            }
+        }
+        if !child_matched {
+            out.extend_from_slice(&self.receivers);
+        }
+        true
+    }
+}
+
+/// Chooses the channels each alert goes to, following `[alerting.route]`
+pub struct Router {
+    root: Route,
+}
+
+impl Router {
+    /// `channels` are the enabled channel instance names, in channel order
+    pub fn new(config: Option<&RouteConfig>, channels: &[String]) -> Result<Self> {
+        let all: Vec<usize> = (0..channels.len()).collect();
+        let root = match config {
+            Some(config) => {
+                if !config.severity.is_empty() || config.metric.is_some()
+                    || !config.labels.is_empty() || config.source.is_some()
+                {
+                    bail!("The root route matches every alert and cannot have matchers; add them to a child route");
+                }
+                Route::build(config, &all, channels)?
+            }
+            None => Route::build(&RouteConfig::default(), &all, channels)?,
+        };
+        Ok(Self { root })
+    }
+
+    /// Indices of the channels that should receive the alert
+    pub fn route(&self, alert: &Alert) -> Vec<usize> {
+        let mut out = Vec::new();
+        self.root.collect(alert, &mut out);
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
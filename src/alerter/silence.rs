@@ -0,0 +1,411 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::config::SilenceConfig;
+use crate::types::{Alert, Label, MetricId, Severity};
+
+/// File in `data_dir` holding silences added at runtime
+const STATE_FILE: &str = "silences.json";
+
+/// One silence with its matchers parsed
+struct Silence {
+    config: SilenceConfig,
+    metric: Option<MetricId>,
+    labels: Vec<Label>,
+    severities: Vec<Severity>,
+    schedule: Option<Schedule>,
+    /// Added at runtime (persisted and removable) rather than configured
+    runtime: bool,
+}
+
+impl Silence {
+    fn compile(config: SilenceConfig, runtime: bool) -> Result<Self> {
+        let metric = config.metric.as_deref()
+            .map(|m| m.parse::<MetricId>().map_err(anyhow::Error::msg))
+            .transpose()
+            .context("Invalid metric in silence")?;
+        let labels = config.labels.iter()
+            .map(|(k, v)| Label { key: k.clone(), value: v.clone() })
+            .collect();
+        let severities = config.severity.iter()
+            .map(|s| match s.as_str() {
+                "info" => Ok(Severity::Info),
+                "warn" => Ok(Severity::Warn),
+                "critical" => Ok(Severity::Critical),
+                "emergency" => Ok(Severity::Emergency),
+                other => bail!("Unknown severity in silence: {}", other),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let schedule = match (&config.schedule, config.duration_mins) {
+            (Some(expr), Some(_)) => Some(Schedule::parse(expr)
+                .with_context(|| format!("Invalid silence schedule: {}", expr))?),
+            (Some(_), None) => bail!("Silence schedule requires duration_mins"),
+            // Relative to what? A configured silence would restart with the agent
+            (None, Some(_)) if !runtime => bail!("Silence duration_mins requires a schedule; use ends_at for a one-off silence"),
+            (None, _) => None,
+        };
+        if let (Some(start), Some(end)) = (config.starts_at, config.ends_at) {
+            if end <= start {
+                bail!("Silence ends_at must be after starts_at");
+            }
+        }
+
+        Ok(Self { config, metric, labels, severities, schedule, runtime })
+    }
+
+    fn matches(&self, alert: &Alert) -> bool {
+        self.metric.is_none_or(|m| m == alert.metric)
+            && self.labels.iter().all(|want| alert.labels.iter().any(|l| l == want))
+            && (self.severities.is_empty() || self.severities.contains(&alert.severity))
+    }
+
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        if self.config.starts_at.is_some_and(|t| now < t) || self.config.ends_at.is_some_and(|t| now >= t) {
+            return false;
+        }
+        match (&self.schedule, self.config.duration_mins) {
+            (Some(schedule), Some(mins)) => {
+                let now = now.with_timezone(&Local);
+                // Open if the schedule fired within the last `duration_mins`
+                let window = chrono::Duration::minutes(mins as i64);
+                schedule.last_fire(now, window).is_some_and(|t| now - t < window)
+            }
+            _ => true,
+        }
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.config.ends_at.is_some_and(|t| now >= t)
+    }
+}
+
+/// Check configured silences without loading runtime ones
+pub fn validate(config: &[SilenceConfig]) -> Result<()> {
+    for (i, c) in config.iter().enumerate() {
+        Silence::compile(c.clone(), false).with_context(|| format!("Invalid silence #{}", i + 1))?;
+    }
+    Ok(())
+}
+
+/// A silence as reported by `list`
+#[derive(Debug, Serialize)]
+pub struct SilenceStatus {
+    #[serde(flatten)]
+    pub config: SilenceConfig,
+    pub active: bool,
+    pub runtime: bool,
+}
+
+/// Silences from config plus those added at runtime, which are persisted in
+/// `data_dir` so they survive restarts
+pub struct Silences {
+    silences: Vec<Silence>,
+    path: PathBuf,
+}
+
+impl Silences {
+    pub fn load(config: &[SilenceConfig], data_dir: &str) -> Result<Self> {
+        let mut silences = config.iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let mut c = c.clone();
+                if c.id.is_empty() {
+                    c.id = format!("config-{}", i);
+                }
+                Silence::compile(c, false).with_context(|| format!("Invalid silence #{}", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let path = PathBuf::from(data_dir).join(STATE_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let saved: Vec<SilenceConfig> = serde_json::from_str(&content)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                for c in saved {
+                    match Silence::compile(c, true) {
+                        Ok(s) => silences.push(s),
+                        Err(e) => tracing::warn!(error = %e, "Dropping invalid saved silence"),
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+
+        let mut loaded = Self { silences, path };
+        loaded.prune_expired();
+        Ok(loaded)
+    }
+
+    /// ID of the first active silence matching the alert
+    pub fn silenced_by(&self, alert: &Alert) -> Option<&str> {
+        let now = Utc::now();
+        self.silences.iter()
+            .find(|s| s.matches(alert) && s.is_active(now))
+            .map(|s| s.config.id.as_str())
+    }
+
+    /// Add a runtime silence and return its ID. Without a schedule,
+    /// `duration_mins` sets `ends_at` relative to the start.
+    pub fn add(&mut self, mut config: SilenceConfig) -> Result<String> {
+        if config.schedule.is_none() {
+            if let Some(mins) = config.duration_mins.take() {
+                let start = config.starts_at.unwrap_or_else(Utc::now);
+                config.ends_at = Some(start + chrono::Duration::minutes(mins as i64));
+            }
+        }
+        if config.metric.is_none() && config.labels.is_empty() && config.severity.is_empty() {
+            bail!("Silence needs at least one matcher (metric, labels or severity)");
+        }
+        if config.id.is_empty() {
+            config.id = format!("{:x}", Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        }
+        if self.silences.iter().any(|s| s.config.id == config.id) {
+            bail!("Silence {} already exists", config.id);
+        }
+
+        let silence = Silence::compile(config, true)?;
+        let id = silence.config.id.clone();
+        tracing::info!(id = %id, comment = %silence.config.comment, "Silence added");
+        self.silences.push(silence);
+        self.save();
+        Ok(id)
+    }
+
+    /// Remove a runtime silence; configured silences can only be removed from config
+    pub fn remove(&mut self, id: &str) -> Result<()> {
+        let Some(pos) = self.silences.iter().position(|s| s.config.id == id) else {
+            bail!("No silence with id {}", id);
+        };
+        if !self.silences[pos].runtime {
+            bail!("Silence {} is defined in the config file", id);
+        }
+        self.silences.remove(pos);
+        tracing::info!(id = %id, "Silence removed");
+        self.save();
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<SilenceStatus> {
+        let now = Utc::now();
+        self.silences.iter()
+            .map(|s| SilenceStatus {
+                config: s.config.clone(),
+                active: s.is_active(now),
+                runtime: s.runtime,
+            })
+            .collect()
+    }
+
+    /// Drop runtime silences whose end time has passed
+    pub fn prune_expired(&mut self) {
+        let now = Utc::now();
+        let before = self.silences.len();
+        self.silences.retain(|s| !(s.runtime && s.is_expired(now)));
+        if self.silences.len() != before {
+            tracing::info!(removed = before - self.silences.len(), "Expired silences removed");
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.try_save() {
+            tracing::error!(error = %e, path = %self.path.display(), "Failed to persist silences");
+        }
+    }
+
+    fn try_save(&self) -> Result<()> {
+        let runtime: Vec<&SilenceConfig> = self.silences.iter()
+            .filter(|s| s.runtime)
+            .map(|s| &s.config)
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&runtime)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Five-field cron expression (minute hour day-of-month month day-of-week)
+/// supporting `*`, lists, ranges and steps. Sunday is 0 or 7.
+struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month / day-of-week were restricted; when both are,
+    /// either may match (standard cron semantics)
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    fn parse(expr: &str) -> Result<Self> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("expected 5 fields, got {}", fields.len());
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        let bit = |mask: u64, n: u32| mask & (1 << n) != 0;
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        let day_ok = if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        };
+        bit(self.months, date.month()) && day_ok
+    }
+
+    /// Latest time at or before `now` the schedule fired, looking back no
+    /// further than `within`
+    fn last_fire(&self, now: DateTime<Local>, within: chrono::Duration) -> Option<DateTime<Local>> {
+        let earliest = (now - within).date_naive();
+        let mut date = now.date_naive();
+        let (mut max_hour, mut max_minute) = (now.hour(), now.minute());
+        while date >= earliest {
+            if self.matches_date(date) {
+                let mut hour = highest_bit(self.hours, max_hour);
+                while let Some(h) = hour {
+                    let mut minute = highest_bit(self.minutes, if h == max_hour { max_minute } else { 59 });
+                    while let Some(m) = minute {
+                        if let Some(t) = local_time(date, h, m, now) {
+                            return Some(t);
+                        }
+                        minute = m.checked_sub(1).and_then(|m| highest_bit(self.minutes, m));
+                    }
+                    hour = h.checked_sub(1).and_then(|h| highest_bit(self.hours, h));
+                }
+            }
+            date = date.pred_opt()?;
+            (max_hour, max_minute) = (23, 59);
+        }
+        None
+    }
+}
+
+/// `date` at `hour:minute` local time and not after `now`: none in a DST gap,
+/// the later of the two when the clock went back
+fn local_time(date: NaiveDate, hour: u32, minute: u32, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let local = date.and_hms_opt(hour, minute, 0)?.and_local_timezone(Local);
+    [local.earliest(), local.latest()].into_iter().flatten().filter(|t| *t <= now).max()
+}
+
+/// Highest value in `mask` no greater than `max`
+fn highest_bit(mask: u64, max: u32) -> Option<u32> {
+    let below = mask & (u64::MAX >> (63 - max));
+    (below != 0).then(|| 63 - below.leading_zeros())
+}
+
+/// Parse one cron field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().with_context(|| format!("bad step in {}", item))?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            bail!("zero step in {}", item);
+        }
+        let (lo, hi) = match range {
+            "*" => (min, max),
+            r => match r.split_once('-') {
+                Some((a, b)) => (a.parse()?, b.parse()?),
+                None => {
+                    let v: u32 = r.parse().with_context(|| format!("bad value {}", r))?;
+                    // `5/15` means from 5 to the end in steps of 15
+                    (v, if item.contains('/') { max } else { v })
+                }
+            },
+        };
+        if lo < min || hi > max || lo > hi {
+            bail!("{} out of range {}-{}", item, min, max);
+        }
+        for v in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_lists_ranges_and_steps() {
+        assert_eq!(parse_field("*/15", 0, 59).unwrap(), 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(parse_field("5/20", 0, 59).unwrap(), 1 << 5 | 1 << 25 | 1 << 45);
+        assert_eq!(parse_field("1-3,9", 0, 23).unwrap(), 1 << 1 | 1 << 2 | 1 << 3 | 1 << 9);
+        // Sunday may be written as 7
+        assert_eq!(Schedule::parse("0 0 * * 7").unwrap().weekdays & 1, 1);
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expr in ["0 0 * *", "60 0 * * *", "0 24 * * *", "*/0 * * * *", "5-1 * * * *", "0 0 0 * *", "x * * * *"] {
+            assert!(Schedule::parse(expr).is_err(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn finds_the_last_fire_within_the_window() {
+        // Saturdays at 02:00; 2025-06-07 is a Saturday
+        let schedule = Schedule::parse("0 2 * * 6").unwrap();
+        let two_hours = chrono::Duration::hours(2);
+
+        assert_eq!(schedule.last_fire(local(2025, 6, 7, 3, 30), two_hours), Some(local(2025, 6, 7, 2, 0)));
+        assert_eq!(schedule.last_fire(local(2025, 6, 7, 2, 0), two_hours), Some(local(2025, 6, 7, 2, 0)));
+        assert_eq!(schedule.last_fire(local(2025, 6, 7, 1, 59), two_hours), None);
+        assert_eq!(schedule.last_fire(local(2025, 6, 8, 3, 0), two_hours), None);
+    }
+
+    #[test]
+    fn looks_back_across_midnight() {
+        let schedule = Schedule::parse("30 23 * * *").unwrap();
+
+        let fired = schedule.last_fire(local(2025, 6, 8, 0, 10), chrono::Duration::hours(1));
+
+        assert_eq!(fired, Some(local(2025, 6, 7, 23, 30)));
+    }
+
+    #[test]
+    fn restricted_day_and_weekday_match_either() {
+        // The 1st of the month and every Monday
+        let schedule = Schedule::parse("0 0 1 * 1").unwrap();
+
+        assert!(schedule.matches_date(NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()));
+        assert!(schedule.matches_date(NaiveDate::from_ymd_opt(2025, 6, 9).unwrap()));
+        assert!(!schedule.matches_date(NaiveDate::from_ymd_opt(2025, 6, 10).unwrap()));
+        // Only the weekday restricted: the day of month does not matter
+        let mondays = Schedule::parse("0 0 * * 1").unwrap();
+        assert!(!mondays.matches_date(NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()));
+    }
+}
@@ -3,19 +3,25 @@ use async_trait::async_trait;
 use serde_json::json;
 
 use crate::config::SlackConfig;
-use crate::types::{Alert, Severity};
-use super::AlertChannel;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::Template;
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+const RESOLVED_COLOR: &str = "good"; // Slack's built-in green
 
 /// Slack webhook alert channel
 pub struct SlackChannel {
+    name: String,
     webhook_url: String,
     channel: Option<String>,
     severity_filter: Vec<Severity>,
+    template: Option<Template>,
     client: reqwest::Client,
 }
 
 impl SlackChannel {
-    pub fn new(config: &SlackConfig) -> Result<Self> {
+    pub fn new(config: &SlackConfig, template: Option<Template>) -> Result<Self> {
         let severity_filter = config.severity_filter.iter()
             .filter_map(|s| match s.as_str() {
                 "info" => Some(Severity::Info),
@@ -27,13 +33,59 @@ impl SlackChannel {
             .collect();
 
         Ok(Self {
+            name: config.name.clone(),
             webhook_url: config.webhook_url.clone(),
             channel: config.channel.clone(),
             severity_filter,
-            client: reqwest::Client::new(),
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    fn attachment(&self, alert: &Alert) -> serde_json::Value {
+        let color = match alert.status {
+            AlertStatus::Firing => Self::severity_color(&alert.severity),
+            AlertStatus::Resolved => RESOLVED_COLOR,
+        };
+        if let Some(ref template) = self.template {
+            return json!({
+                "color": color,
+                "text": template.render(alert, escape_mrkdwn),
+                "ts": alert.timestamp.timestamp(),
+            });
+        }
+
+        let mut fields = vec![
+            json!({ "title": "Host", "value": &alert.hostname, "short": true }),
+            json!({ "title": "Metric", "value": alert.metric.to_string(), "short": true }),
+            json!({ "title": "Value", "value": format!("{:.2}", alert.value), "short": true }),
+        ];
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            fields.push(json!({ "title": "Duration", "value": format_duration(d), "short": true }));
+        }
+
+        json!({
+            "color": color,
+            "title": format!("{} {}", status_tag(alert), alert.message),
+            "fields": fields,
+            "ts": alert.timestamp.timestamp(),
         })
     }
 
+    async fn post(&self, mut payload: serde_json::Value) -> Result<()> {
+        if let Some(ref ch) = self.channel {
+            payload["channel"] = json!(ch);
+        }
+
+        let resp = self.client.post(&self.webhook_url)
+            .json(&payload)
+            .send()
+            .await?;
+        check_status(resp)?;
+
+        Ok(())
+    }
+
     fn severity_color(severity: &Severity) -> &'static str {
         match severity {
             Severity::Info => "#2ecc71",
@@ -44,38 +96,37 @@ impl SlackChannel {
     }
 }
 
+/// Slack treats `&`, `<` and `>` as control characters in message text
+fn escape_mrkdwn(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 #[async_trait]
 impl AlertChannel for SlackChannel {
-    fn name(&self) -> &str { "slack" }
+    fn name(&self) -> &str { &self.name }
 
     fn accepts_severity(&self, severity: &Severity) -> bool {
         self.severity_filter.is_empty() || self.severity_filter.contains(severity)
     }
 
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
     async fn send(&self, alert: &Alert) -> Result<()> {
-        let mut payload = json!({
-            "attachments": [{
-                "color": Self::severity_color(&alert.severity),
-                "title": format!("[{}] {}", alert.severity, alert.message),
-                "fields": [
-                    { "title": "Host", "value": &alert.hostname, "short": true },
-                    { "title": "Metric", "value": alert.metric.to_string(), "short": true },
-                    { "title": "Value", "value": format!("{:.2}", alert.value), "short": true },
-                ],
-                "ts": alert.timestamp.timestamp(),
-            }]
+        let payload = json!({
+            "attachments": [self.attachment(alert)],
         });
+        self.post(payload).await
+    }
 
-        if let Some(ref ch) = self.channel {
-            payload["channel"] = json!(ch);
-        }
-
-        self.client.post(&self.webhook_url)
-            .json(&payload)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let payload = json!({
+            "text": format!("*{}*", group_summary(alerts)),
+            "attachments": alerts.iter().map(|a| self.attachment(a)).collect::<Vec<_>>(),
+        });
+        self.post(payload).await
     }
 }
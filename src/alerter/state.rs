@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use super::active::ActiveAlert;
+use super::persist::FileWriter;
+use super::rate_limit::SavedLimiter;
+use super::DeduplicationKey;
+
+/// File in `data_dir` holding the alert manager's state
+const STATE_FILE: &str = "alert_state.json";
+
+/// What the alert manager needs to carry over a restart: the conditions
+/// being tracked, when each was last notified and the channels' rate limits
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedState {
+    pub saved_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub active: Vec<ActiveAlert>,
+    #[serde(default)]
+    pub dedup: Vec<SavedDedup>,
+    #[serde(default)]
+    pub limiters: Vec<SavedLimiter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedDedup {
+    pub key: DeduplicationKey,
+    pub last_sent: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub count: u32,
+}
+
+/// Alert state saved in `data_dir`, written by the file writer thread
+pub struct StateFile {
+    path: PathBuf,
+    writer: FileWriter,
+}
+
+impl StateFile {
+    pub fn new(data_dir: &str, writer: FileWriter) -> Self {
+        Self { path: PathBuf::from(data_dir).join(STATE_FILE), writer }
+    }
+
+    /// The saved state, or an empty one when there is none. A state file
+    /// that cannot be read is logged and ignored rather than blocking startup.
+    pub fn load(&self) -> SavedState {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(state) => return state,
+                Err(e) => tracing::warn!(error = %e, path = %self.path.display(), "Ignoring unreadable alert state"),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!(error = %e, path = %self.path.display(), "Failed to read alert state"),
+        }
+        SavedState::default()
+    }
+
+    /// Serialize the state and queue it for writing
+    pub fn save(&self, state: &SavedState) {
+        match serde_json::to_vec(state) {
+            Ok(contents) => self.writer.save(&self.path, contents, "alert state"),
+            Err(e) => tracing::error!(error = %e, "Failed to serialize alert state"),
+        }
+    }
+}
+
+/// Wall-clock time of a monotonic instant, for saving
+pub fn to_wall_clock(t: Instant) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::from_std(t.elapsed()).unwrap_or_default()
+}
+
+/// Monotonic instant of a saved wall-clock time, or `None` for a time from
+/// before the monotonic clock's origin (e.g. before a reboot). Callers treat
+/// windows measured from such a time as expired.
+pub fn to_instant(t: DateTime<Utc>) -> Option<Instant> {
+    let ago = (Utc::now() - t).to_std().unwrap_or_default();
+    Instant::now().checked_sub(ago)
+}
@@ -0,0 +1,268 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{lookup_host, TcpStream, UdpSocket, UnixDatagram};
+
+use crate::config::SyslogConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::template::{no_escape, Template};
+use super::AlertChannel;
+
+/// Enterprise number used for structured data IDs (RFC 5612 documentation PEN)
+const SD_ENTERPRISE: &str = "32473";
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Syslog alert channel writing RFC 5424 messages with structured data to
+/// the local socket, falling back to a remote collector when configured
+pub struct SyslogChannel {
+    name: String,
+    facility: u8,
+    socket_path: String,
+    remote: Option<(String, Transport)>,
+    app_name: String,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl SyslogChannel {
+    pub fn new(config: &SyslogConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        let facility = parse_facility(&config.facility)
+            .with_context(|| format!("Unknown syslog facility: {}", config.facility))?;
+
+        let remote = match config.remote {
+            Some(ref addr) => {
+                let transport = match config.protocol.to_ascii_lowercase().as_str() {
+                    "udp" => Transport::Udp,
+                    "tcp" => Transport::Tcp,
+                    other => bail!("Unsupported syslog protocol: {} (expected udp or tcp)", other),
+                };
+                Some((addr.clone(), transport))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            name: config.name.clone(),
+            facility,
+            socket_path: config.socket_path.clone(),
+            remote,
+            app_name: sanitize_header(&config.app_name, 48),
+            severity_filter,
+            template,
+        })
+    }
+
+    fn severity_code(severity: &Severity) -> u8 {
+        match severity {
+            Severity::Info => 6,      // LOG_INFO
+            Severity::Warn => 4,      // LOG_WARNING
+            Severity::Critical => 2,  // LOG_CRIT
+            Severity::Emergency => 0, // LOG_EMERG
+        }
+    }
+
+    /// Format an alert as an RFC 5424 message
+    fn format_message(&self, alert: &Alert) -> String {
+        // Recoveries are logged at LOG_NOTICE regardless of the original severity
+        let (code, msgid) = match alert.status {
+            AlertStatus::Firing => (Self::severity_code(&alert.severity), "ALERT"),
+            AlertStatus::Resolved => (5, "RESOLVED"),
+        };
+        let pri = self.facility as u32 * 8 + code as u32;
+
+        let mut sd = format!(
+            "[alert@{} metric=\"{}\" value=\"{}\" severity=\"{}\" status=\"{}\" source=\"{}\"",
+            SD_ENTERPRISE,
+            escape_param(&alert.metric.to_string()),
+            alert.value,
+            alert.severity,
+            match alert.status {
+                AlertStatus::Firing => "firing",
+                AlertStatus::Resolved => "resolved",
+            },
+            escape_param(&alert.source),
+        );
+        if let Some(threshold) = alert.threshold {
+            sd.push_str(&format!(" threshold=\"{}\"", threshold));
+        }
+        if let Some(original) = alert.escalated_from {
+            sd.push_str(&format!(" escalated_from=\"{}\"", original));
+        }
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            sd.push_str(&format!(" duration_secs=\"{}\"", d.num_seconds()));
+        }
+        sd.push(']');
+        if !alert.labels.is_empty() {
+            sd.push_str(&format!("[labels@{}", SD_ENTERPRISE));
+            for label in &alert.labels {
+                sd.push_str(&format!(" {}=\"{}\"", sd_name(&label.key), escape_param(&label.value)));
+            }
+            sd.push(']');
+        }
+
+        // Syslog messages are single-line
+        let text = match self.template {
+            Some(ref template) => template.render(alert, no_escape).lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            None => alert.message.clone(),
+        };
+
+        format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            pri,
+            alert.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            sanitize_header(&alert.hostname, 255),
+            self.app_name,
+            std::process::id(),
+            msgid,
+            sd,
+            text,
+        )
+    }
+
+    async fn send_local(&self, message: &str) -> Result<()> {
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to(message.as_bytes(), &self.socket_path).await
+            .with_context(|| format!("Failed to write to {}", self.socket_path))?;
+        Ok(())
+    }
+
+    async fn send_remote(&self, addr: &str, transport: Transport, message: &str) -> Result<()> {
+        match transport {
+            Transport::Udp => {
+                let remote = tokio::time::timeout(NETWORK_TIMEOUT, lookup_host(addr))
+                    .await
+                    .context("Timed out resolving remote syslog")??
+                    .next()
+                    .with_context(|| format!("No address found for {}", addr))?;
+                // Bind in the remote's address family, or sending to an IPv6 remote fails
+                let local = if remote.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+                let socket = UdpSocket::bind(local).await?;
+                socket.send_to(message.as_bytes(), remote).await?;
+            }
+            Transport::Tcp => {
+                let mut stream = tokio::time::timeout(NETWORK_TIMEOUT, TcpStream::connect(addr))
+                    .await
+                    .context("Timed out connecting to remote syslog")??;
+                // Octet-counting framing (RFC 6587)
+                let frame = format!("{} {}", message.len(), message);
+                tokio::time::timeout(NETWORK_TIMEOUT, stream.write_all(frame.as_bytes()))
+                    .await
+                    .context("Timed out writing to remote syslog")??;
+                stream.shutdown().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_facility(name: &str) -> Option<u8> {
+    let code = match name.to_ascii_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "mail" => 2,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "lpr" => 6,
+        "news" => 7,
+        "uucp" => 8,
+        "cron" => 9,
+        "authpriv" => 10,
+        "ftp" => 11,
+        "local0" => 16,
+        "local1" => 17,
+        "local2" => 18,
+        "local3" => 19,
+        "local4" => 20,
+        "local5" => 21,
+        "local6" => 22,
+        "local7" => 23,
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Header fields are printable US-ASCII without spaces; empty becomes NILVALUE
+fn sanitize_header(s: &str, max_len: usize) -> String {
+    let out: String = s.chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if out.is_empty() { "-".into() } else { out }
+}
+
+/// SD-NAME: up to 32 printable characters excluding '=', ' ', ']' and '"'
+fn sd_name(s: &str) -> String {
+    let out: String = s.chars()
+        .map(|c| if c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"') { c } else { '_' })
+        .take(32)
+        .collect();
+    if out.is_empty() { "_".into() } else { out }
+}
+
+/// PARAM-VALUE escaping: '"', '\' and ']' are backslash-escaped
+fn escape_param(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[async_trait]
+impl AlertChannel for SyslogChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let message = self.format_message(alert);
+
+        let local_err = if self.socket_path.is_empty() {
+            None
+        } else {
+            match self.send_local(&message).await {
+                Ok(()) => return Ok(()),
+                Err(e) => Some(e),
+            }
+        };
+
+        match (&self.remote, local_err) {
+            (Some((addr, transport)), local_err) => {
+                if let Some(e) = local_err {
+                    tracing::debug!(error = %e, remote = %addr, "Local syslog unavailable, using remote");
+                }
+                self.send_remote(addr, *transport, &message).await
+                    .with_context(|| format!("Failed to send to remote syslog {}", addr))
+            }
+            (None, Some(e)) => Err(e),
+            (None, None) => bail!("Syslog channel has neither socket_path nor remote configured"),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::config::TeamsConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::check_status;
+use super::template::{no_escape, Template};
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+/// Teams renders Adaptive Cards up to schema 1.5; 1.4 also works in Workflows
+const CARD_VERSION: &str = "1.4";
+
+/// Microsoft Teams alert channel, posting Adaptive Cards to an incoming
+/// webhook or a Workflows webhook trigger
+pub struct TeamsChannel {
+    name: String,
+    webhook_url: String,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    client: reqwest::Client,
+}
+
+impl TeamsChannel {
+    pub fn new(config: &TeamsConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        Ok(Self {
+            name: config.name.clone(),
+            webhook_url: config.webhook_url.clone(),
+            severity_filter,
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+        })
+    }
+
+    /// One alert as a container whose style gives it the severity's color
+    fn container(&self, alert: &Alert) -> serde_json::Value {
+        let (style, color) = match alert.status {
+            AlertStatus::Firing => Self::severity_style(&alert.severity),
+            AlertStatus::Resolved => ("good", "Good"),
+        };
+        if let Some(ref template) = self.template {
+            return json!({
+                "type": "Container",
+                "style": style,
+                "bleed": true,
+                "items": [{ "type": "TextBlock", "text": template.render(alert, no_escape), "wrap": true }],
+            });
+        }
+
+        let mut facts = vec![
+            json!({ "title": "Host", "value": &alert.hostname }),
+            json!({ "title": "Metric", "value": alert.metric.to_string() }),
+            json!({ "title": "Value", "value": format!("{:.2}", alert.value) }),
+        ];
+        if let (AlertStatus::Resolved, Some(d)) = (alert.status, alert.active_duration()) {
+            facts.push(json!({ "title": "Duration", "value": format_duration(d) }));
+        }
+        facts.push(json!({ "title": "Time", "value": alert.timestamp.to_rfc3339() }));
+
+        json!({
+            "type": "Container",
+            "style": style,
+            "bleed": true,
+            "items": [
+                {
+                    "type": "TextBlock",
+                    "text": format!("{} {}", status_tag(alert), alert.message),
+                    "weight": "Bolder",
+                    "color": color,
+                    "wrap": true,
+                },
+                { "type": "FactSet", "facts": facts },
+            ],
+        })
+    }
+
+    /// Webhook message carrying a single Adaptive Card
+    fn message(body: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "type": "message",
+            "attachments": [{
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "contentUrl": null,
+                "content": {
+                    "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                    "type": "AdaptiveCard",
+                    "version": CARD_VERSION,
+                    "msteams": { "width": "Full" },
+                    "body": body,
+                },
+            }],
+        })
+    }
+
+    async fn post(&self, payload: &serde_json::Value) -> Result<()> {
+        let resp = self.client.post(&self.webhook_url)
+            .json(payload)
+            .send()
+            .await?;
+        check_status(resp)?;
+        Ok(())
+    }
+
+    /// Adaptive Cards only offer named colors: container style and text color
+    fn severity_style(severity: &Severity) -> (&'static str, &'static str) {
+        match severity {
+            Severity::Info => ("accent", "Accent"),
+            Severity::Warn => ("warning", "Warning"),
+            Severity::Critical => ("attention", "Attention"),
+            Severity::Emergency => ("attention", "Attention"),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for TeamsChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.post(&Self::message(vec![self.container(alert)])).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let mut body = vec![json!({
+            "type": "TextBlock",
+            "text": group_summary(alerts),
+            "size": "Medium",
+            "weight": "Bolder",
+            "wrap": true,
+        })];
+        body.extend(alerts.iter().map(|a| self.container(a)));
+        self.post(&Self::message(body)).await
+    }
+}
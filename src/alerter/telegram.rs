@@ -0,0 +1,418 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::TelegramConfig;
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::RateLimited;
+use super::template::{no_escape, Template};
+use super::{format_duration, group_summary, http_client_builder, status_tag, AlertChannel};
+
+/// sendMessage accepts up to 4096 characters; leave room for entity markup
+const MAX_MESSAGE_LEN: usize = 4000;
+
+/// Telegram Bot API alert channel
+pub struct TelegramChannel {
+    name: String,
+    /// `<api_url>/bot<token>/sendMessage`; never logged since it embeds the token
+    send_url: String,
+    chat_id: String,
+    parse_mode: ParseMode,
+    severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    client: reqwest::Client,
+    /// Hashes of the messages of a split batch already delivered, so a retry
+    /// after a later message failed does not send them again
+    delivered: Mutex<HashSet<u64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseMode {
+    Html,
+    MarkdownV2,
+    Plain,
+}
+
+/// Envelope returned by every Bot API method
+#[derive(Deserialize)]
+struct ApiResponse {
+    ok: bool,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    /// Seconds to wait after a flood-control 429
+    retry_after: Option<u64>,
+}
+
+impl TelegramChannel {
+    pub fn new(config: &TelegramConfig, template: Option<Template>) -> Result<Self> {
+        let severity_filter = config.severity_filter.iter()
+            .filter_map(|s| match s.as_str() {
+                "info" => Some(Severity::Info),
+                "warn" => Some(Severity::Warn),
+                "critical" => Some(Severity::Critical),
+                "emergency" => Some(Severity::Emergency),
+                _ => None,
+            })
+            .collect();
+
+        let parse_mode = match config.parse_mode.to_ascii_lowercase().as_str() {
+            "html" => ParseMode::Html,
+            "markdownv2" => ParseMode::MarkdownV2,
+            "" | "none" | "plain" => ParseMode::Plain,
+            other => bail!("Unsupported Telegram parse_mode: {} (expected HTML, MarkdownV2 or none)", other),
+        };
+
+        Ok(Self {
+            name: config.name.clone(),
+            send_url: format!("{}/bot{}/sendMessage", config.api_url.trim_end_matches('/'), config.bot_token),
+            chat_id: config.chat_id.clone(),
+            parse_mode,
+            severity_filter,
+            template,
+            client: http_client_builder(config.timeout_secs).build()?,
+            delivered: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn severity_icon(severity: &Severity) -> &'static str {
+        match severity {
+            Severity::Info => "🟢",
+            Severity::Warn => "🟡",
+            Severity::Critical => "🔴",
+            Severity::Emergency => "🟣",
+        }
+    }
+
+    fn escape(&self, s: &str) -> String {
+        match self.parse_mode {
+            ParseMode::Html => escape_html(s),
+            ParseMode::MarkdownV2 => escape_markdown_v2(s),
+            ParseMode::Plain => s.to_string(),
+        }
+    }
+
+    /// Monospace spans only need ` and \ escaped in MarkdownV2
+    fn code(&self, s: &str) -> String {
+        match self.parse_mode {
+            ParseMode::Html => format!("<code>{}</code>", escape_html(s)),
+            ParseMode::MarkdownV2 => format!("`{}`", s.replace('\\', "\\\\").replace('`', "\\`")),
+            ParseMode::Plain => s.to_string(),
+        }
+    }
+
+    fn bold(&self, s: &str) -> String {
+        match self.parse_mode {
+            ParseMode::Html => format!("<b>{}</b>", escape_html(s)),
+            ParseMode::MarkdownV2 => format!("*{}*", escape_markdown_v2(s)),
+            ParseMode::Plain => s.to_string(),
+        }
+    }
+
+    fn format_message(&self, alert: &Alert) -> String {
+        if let Some(ref template) = self.template {
+            let escape = match self.parse_mode {
+                ParseMode::Html => escape_html,
+                ParseMode::MarkdownV2 => escape_markdown_v2,
+                ParseMode::Plain => no_escape,
+            };
+            return template.render(alert, escape);
+        }
+
+        let mut value = format!("{:.2}", alert.value);
+        if let Some(threshold) = alert.threshold {
+            value.push_str(&format!(" (threshold {:.2})", threshold));
+        }
+
+        let icon = match alert.status {
+            AlertStatus::Firing => Self::severity_icon(&alert.severity),
+            AlertStatus::Resolved => "✅",
+        };
+        let mut lines = vec![
+            format!("{} {}", icon, self.bold(&format!("{} {}", status_tag(alert), alert.message))),
+            format!("{} {}", self.escape("Host:"), self.code(&alert.hostname)),
+            format!("{} {}", self.escape("Metric:"), self.code(&alert.metric.to_string())),
+            format!("{} {}", self.escape("Value:"), self.escape(&value)),
+        ];
+        if !alert.labels.is_empty() {
+            let labels = alert.labels.iter()
+                .map(|l| format!("{}={}", l.key, l.value))
+                .collect::<Vec<_>>()
+                .join(", ");
+            lines.push(format!("{} {}", self.escape("Labels:"), self.code(&labels)));
+        }
+        if alert.status == AlertStatus::Resolved {
+            if let Some(d) = alert.active_duration() {
+                lines.push(format!("{} {}", self.escape("Duration:"), self.escape(&format_duration(d))));
+            }
+        }
+        lines.push(format!("{} {}", self.escape("Time:"), self.escape(&alert.timestamp.to_rfc3339())));
+        lines.join("\n")
+    }
+
+    /// The alert formatted to fit one message. An overlong alert message
+    /// (e.g. a long log line) is cut before formatting, so the markup around
+    /// it stays valid.
+    fn format_block(&self, alert: &Alert) -> String {
+        let block = self.format_message(alert);
+        let excess = block.chars().count().saturating_sub(MAX_MESSAGE_LEN);
+        if excess == 0 {
+            return block;
+        }
+        // Each character cut shortens the block by at least one; one more for the ellipsis
+        let keep = alert.message.chars().count().saturating_sub(excess + 1);
+        let mut shortened = alert.clone();
+        shortened.message = alert.message.chars().take(keep).chain(std::iter::once('…')).collect();
+        let block = self.format_message(&shortened);
+        if block.chars().count() <= MAX_MESSAGE_LEN {
+            return block;
+        }
+        // Long for another reason, e.g. a template not showing the message
+        self.escape(&format!("{} {} on {}: alert too long to show", status_tag(alert), alert.metric, alert.hostname))
+    }
+
+    /// The messages for a group: a summary, then the alerts, split so that
+    /// none exceeds the length limit
+    fn batch_messages(&self, alerts: &[Alert]) -> Vec<String> {
+        let mut messages = Vec::new();
+        let mut text = self.bold(&group_summary(alerts));
+        for alert in alerts {
+            let block = self.format_block(alert);
+            if text.chars().count() + block.chars().count() + 2 > MAX_MESSAGE_LEN {
+                messages.push(std::mem::take(&mut text));
+            }
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(&block);
+        }
+        messages.push(text);
+        messages
+    }
+}
+
+impl TelegramChannel {
+    async fn send_text(&self, text: &str) -> Result<()> {
+        let mut payload = json!({
+            "chat_id": &self.chat_id,
+            "text": text,
+            "disable_web_page_preview": true,
+        });
+        match self.parse_mode {
+            ParseMode::Html => payload["parse_mode"] = json!("HTML"),
+            ParseMode::MarkdownV2 => payload["parse_mode"] = json!("MarkdownV2"),
+            ParseMode::Plain => {}
+        }
+
+        // Strip the URL from transport errors so the bot token never reaches the logs
+        let resp = self.client.post(&self.send_url)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| e.without_url())?;
+        let status = resp.status();
+        let Ok(body) = resp.json::<ApiResponse>().await else {
+            bail!("Telegram API returned {} with an unreadable body", status);
+        };
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = body.parameters.and_then(|p| p.retry_after).map(Duration::from_secs);
+            return Err(RateLimited { retry_after }.into());
+        }
+        if !body.ok {
+            bail!(
+                "Telegram API error ({}): {}",
+                status,
+                body.description.unwrap_or_else(|| "no description".into())
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Escape text for `parse_mode=HTML`
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escape text outside entities for `parse_mode=MarkdownV2`
+fn escape_markdown_v2(s: &str) -> String {
+    const SPECIAL: &str = "_*[]()~`>#+-=|{}.!\\";
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if SPECIAL.contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[async_trait]
+impl AlertChannel for TelegramChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        true
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.send_text(&self.format_block(alert)).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let messages = self.batch_messages(alerts);
+        let keys: Vec<u64> = messages.iter().map(|text| {
+            let mut hasher = DefaultHasher::new();
+            text.hash(&mut hasher);
+            hasher.finish()
+        }).collect();
+        for (text, key) in messages.iter().zip(&keys) {
+            if self.delivered.lock().unwrap().contains(key) {
+                continue;
+            }
+            self.send_text(text).await?;
+            self.delivered.lock().unwrap().insert(*key);
+        }
+        // The whole group is out; retries of it are over
+        let mut delivered = self.delivered.lock().unwrap();
+        for key in &keys {
+            delivered.remove(key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MetricId;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn channel(api_url: &str, parse_mode: &str) -> TelegramChannel {
+        let config: TelegramConfig = toml::from_str(&format!(
+            "bot_token = \"123:abc\"\nchat_id = \"42\"\nparse_mode = \"{}\"\napi_url = \"{}\"",
+            parse_mode, api_url,
+        )).unwrap();
+        TelegramChannel::new(&config, None).unwrap()
+    }
+
+    fn alert(message: &str) -> Alert {
+        Alert {
+            timestamp: chrono::Utc::now(),
+            severity: Severity::Warn,
+            metric: MetricId::CpuUsage,
+            value: 91.0,
+            threshold: Some(90.0),
+            message: message.into(),
+            labels: Default::default(),
+            hostname: "web-01".into(),
+            source: "log".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    /// Bot API stand-in recording the texts it was sent; the request
+    /// numbered `fail` (from 0) gets a server error
+    async fn serve(fail: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let texts = Arc::new(Mutex::new(Vec::new()));
+        let received = texts.clone();
+        tokio::spawn(async move {
+            for n in 0.. {
+                let Ok((mut conn, _)) = listener.accept().await else { return };
+                let mut request = Vec::new();
+                let mut buf = [0u8; 65536];
+                let body = loop {
+                    let read = conn.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    let Some((head, body)) = text.split_once("\r\n\r\n") else { continue };
+                    let len: usize = head.lines()
+                        .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length: ").map(|v| v.parse().unwrap()))
+                        .unwrap_or(0);
+                    if body.len() >= len {
+                        break body.to_string();
+                    }
+                };
+                let (status, reply) = if n == fail {
+                    ("500 Internal Server Error", r#"{"ok":false,"description":"try again"}"#)
+                } else {
+                    let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+                    received.lock().unwrap().push(payload["text"].as_str().unwrap().to_string());
+                    ("200 OK", r#"{"ok":true}"#)
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, reply.len(), reply,
+                );
+                conn.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, texts)
+    }
+
+    #[test]
+    fn cuts_an_overlong_message_keeping_the_markup() {
+        let channel = channel("http://127.0.0.1:9", "HTML");
+
+        let block = channel.format_block(&alert(&"<x>".repeat(2000)));
+
+        assert!(block.chars().count() <= MAX_MESSAGE_LEN);
+        assert!(block.contains("…</b>"));
+        assert!(block.contains("<code>web-01</code>"));
+    }
+
+    #[test]
+    fn splits_a_group_below_the_length_limit() {
+        let channel = channel("http://127.0.0.1:9", "MarkdownV2");
+        let alerts: Vec<Alert> = (0..20).map(|i| alert(&format!("line {} {}", i, "x".repeat(900)))).collect();
+
+        let messages = channel.batch_messages(&alerts);
+
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|m| m.chars().count() <= MAX_MESSAGE_LEN));
+        assert!(messages[0].starts_with("*20 cpu alerts"));
+    }
+
+    #[tokio::test]
+    async fn retry_sends_only_the_messages_not_yet_delivered() {
+        let (url, texts) = serve(1).await;
+        let channel = channel(&url, "none");
+        let alerts: Vec<Alert> = (0..10).map(|i| alert(&format!("alert {} {}", i, "x".repeat(1500)))).collect();
+        let messages = channel.batch_messages(&alerts);
+        assert!(messages.len() >= 3);
+
+        assert!(channel.send_batch(&alerts).await.is_err());
+        channel.send_batch(&alerts).await.unwrap();
+
+        assert_eq!(*texts.lock().unwrap(), messages);
+        assert!(channel.delivered.lock().unwrap().is_empty());
+    }
+}
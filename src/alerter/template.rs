@@ -0,0 +1,494 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::AlertingConfig;
+use crate::types::{Alert, AlertStatus};
+use super::{format_duration, grouping::metric_family};
+
+/// Channel types accepted as keys in `[alerting.templates]`, besides
+/// `default` and channel instance names
+const CHANNEL_TYPES: &[&str] = &[
+    "discord", "slack", "telegram", "email", "webhook", "syslog", "pagerduty",
+    "teams", "mattermost", "google_chat", "ntfy",
+];
+
+/// Variables a template may reference; `labels.<key>` is also accepted
+const VARIABLES: &[&str] = &[
+    "hostname", "severity", "status", "resolved", "metric", "value", "threshold", "message",
+    "source", "timestamp", "started_at", "duration", "escalated_from", "labels", "runbook_url",
+];
+
+/// Escaping applied to substituted values, so they cannot break the
+/// channel's markup; literal template text is emitted as written
+pub type Escape = fn(&str) -> String;
+
+pub fn no_escape(s: &str) -> String {
+    s.to_string()
+}
+
+/// Message template with `{{variable | filter}}` substitution and
+/// `{{#if cond}}...{{else}}...{{/if}}` conditionals
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+    runbooks: Arc<Runbooks>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var(Expr),
+    If { cond: Cond, then: Vec<Node>, otherwise: Vec<Node> },
+}
+
+/// An `{{#if}}` being parsed: the nodes before it and, once `{{else}}` is
+/// seen, its then-branch
+struct Block {
+    cond: Cond,
+    outer: Vec<Node>,
+    then: Option<Vec<Node>>,
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    var: String,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug, Clone)]
+enum Filter {
+    /// Fixed number of decimals
+    Number(usize),
+    /// Binary units, e.g. `1.5 GiB`
+    Bytes,
+    /// Seconds as `1h 5m 3s`
+    Duration,
+    Upper,
+    Lower,
+    /// Fallback when the variable is missing or empty
+    Default(String),
+}
+
+#[derive(Debug, Clone)]
+enum Cond {
+    Truthy(String),
+    /// Case-insensitive comparison with a literal; `negate` for `!=`
+    Eq { var: String, literal: String, negate: bool },
+}
+
+/// A substituted value before formatting
+enum Value {
+    Str(String),
+    Num(f64),
+    Missing,
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Str(s) => !s.is_empty(),
+            Value::Num(n) => *n != 0.0,
+            Value::Missing => false,
+        }
+    }
+
+    fn into_string(self) -> String {
+        match self {
+            Value::Str(s) => s,
+            Value::Num(n) => format_number(n),
+            Value::Missing => String::new(),
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        Self::parse_with(source, Arc::new(Runbooks::default()))
+    }
+
+    fn parse_with(source: &str, runbooks: Arc<Runbooks>) -> Result<Self> {
+        let mut stack: Vec<Block> = Vec::new();
+        let mut current: Vec<Node> = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                current.push(Node::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after.find("}}").context("Unclosed `{{` in template")?;
+            let tag = after[..end].trim();
+            rest = &after[end + 2..];
+
+            if let Some(cond) = tag.strip_prefix("#if ") {
+                stack.push(Block {
+                    cond: parse_cond(cond.trim())?,
+                    outer: std::mem::take(&mut current),
+                    then: None,
+                });
+            } else if tag == "else" {
+                let block = stack.last_mut().context("`{{else}}` outside `{{#if}}`")?;
+                if block.then.is_some() {
+                    bail!("Duplicate `{{{{else}}}}` in `{{{{#if}}}}` block");
+                }
+                block.then = Some(std::mem::take(&mut current));
+            } else if tag == "/if" {
+                let block = stack.pop().context("`{{/if}}` without `{{#if}}`")?;
+                let branch = std::mem::replace(&mut current, block.outer);
+                let (then, otherwise) = match block.then {
+                    Some(then) => (then, branch),
+                    None => (branch, Vec::new()),
+                };
+                current.push(Node::If { cond: block.cond, then, otherwise });
+            } else {
+                current.push(Node::Var(parse_expr(tag)?));
+            }
+        }
+        if !rest.is_empty() {
+            current.push(Node::Text(rest.to_string()));
+        }
+        if !stack.is_empty() {
+            bail!("Unclosed `{{{{#if}}}}` in template");
+        }
+
+        Ok(Self { nodes: current, runbooks })
+    }
+
+    /// Render for one alert, escaping substituted values with `escape`
+    pub fn render(&self, alert: &Alert, escape: Escape) -> String {
+        let mut out = String::new();
+        self.render_nodes(&self.nodes, alert, escape, &mut out);
+        out
+    }
+
+    fn render_nodes(&self, nodes: &[Node], alert: &Alert, escape: Escape, out: &mut String) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var(expr) => {
+                    let value = expr.filters.iter()
+                        .fold(self.lookup(&expr.var, alert), apply_filter)
+                        .into_string();
+                    out.push_str(&escape(&value));
+                }
+                Node::If { cond, then, otherwise } => {
+                    let holds = match cond {
+                        Cond::Truthy(var) => self.lookup(var, alert).is_truthy(),
+                        Cond::Eq { var, literal, negate } => {
+                            self.lookup(var, alert).into_string().eq_ignore_ascii_case(literal) != *negate
+                        }
+                    };
+                    self.render_nodes(if holds { then } else { otherwise }, alert, escape, out);
+                }
+            }
+        }
+    }
+
+    fn lookup(&self, var: &str, alert: &Alert) -> Value {
+        let resolved = alert.status == AlertStatus::Resolved;
+        match var {
+            "hostname" => Value::Str(alert.hostname.clone()),
+            "severity" => Value::Str(alert.severity.to_string()),
+            "status" => Value::Str(if resolved { "resolved" } else { "firing" }.into()),
+            "resolved" if resolved => Value::Str("true".into()),
+            "resolved" => Value::Missing,
+            "metric" => Value::Str(alert.metric.to_string()),
+            "value" => Value::Num(alert.value),
+            "threshold" => alert.threshold.map_or(Value::Missing, Value::Num),
+            "message" => Value::Str(alert.message.clone()),
+            "source" => Value::Str(alert.source.clone()),
+            "timestamp" => Value::Str(alert.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            "started_at" => alert.started_at
+                .map_or(Value::Missing, |t| Value::Str(t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))),
+            "duration" => alert.active_duration()
+                .map_or(Value::Missing, |d| Value::Num(d.num_seconds() as f64)),
+            "escalated_from" => alert.escalated_from.map_or(Value::Missing, |s| Value::Str(s.to_string())),
+            "labels" if alert.labels.is_empty() => Value::Missing,
+            "labels" => Value::Str(alert.labels.iter()
+                .map(|l| format!("{}={}", l.key, l.value))
+                .collect::<Vec<_>>()
+                .join(", ")),
+            "runbook_url" => self.runbooks.url_for(alert).map_or(Value::Missing, Value::Str),
+            _ => match var.strip_prefix("labels.") {
+                Some(key) => alert.labels.iter()
+                    .find(|l| l.key == key)
+                    .map_or(Value::Missing, |l| Value::Str(l.value.clone())),
+                None => Value::Missing,
+            },
+        }
+    }
+}
+
+fn check_variable(var: &str) -> Result<()> {
+    if VARIABLES.contains(&var) || var.strip_prefix("labels.").is_some_and(|k| !k.is_empty()) {
+        Ok(())
+    } else {
+        bail!("Unknown template variable `{}`", var)
+    }
+}
+
+fn parse_expr(tag: &str) -> Result<Expr> {
+    let mut parts = tag.split('|').map(str::trim);
+    let var = parts.next().unwrap_or_default().to_string();
+    check_variable(&var)?;
+    let filters = parts
+        .map(|f| {
+            let (name, arg) = match f.split_once(':') {
+                Some((n, a)) => (n.trim(), Some(a.trim())),
+                None => (f, None),
+            };
+            Ok(match (name, arg) {
+                ("number", None) => Filter::Number(2),
+                ("number", Some(n)) => Filter::Number(n.parse()
+                    .with_context(|| format!("Invalid decimals for `number`: {}", n))?),
+                ("bytes", None) => Filter::Bytes,
+                ("duration", None) => Filter::Duration,
+                ("upper", None) => Filter::Upper,
+                ("lower", None) => Filter::Lower,
+                ("default", Some(s)) => Filter::Default(unquote(s).to_string()),
+                _ => bail!("Unknown template filter `{}`", f),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Expr { var, filters })
+}
+
+fn parse_cond(cond: &str) -> Result<Cond> {
+    let (var, literal, negate) = if let Some((v, l)) = cond.split_once("!=") {
+        (v, Some(l), true)
+    } else if let Some((v, l)) = cond.split_once("==") {
+        (v, Some(l), false)
+    } else {
+        (cond, None, false)
+    };
+    let var = var.trim().to_string();
+    check_variable(&var)?;
+    Ok(match literal {
+        Some(l) => Cond::Eq { var, literal: unquote(l.trim()).to_string(), negate },
+        None => Cond::Truthy(var),
+    })
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(s)
+}
+
+fn apply_filter(value: Value, filter: &Filter) -> Value {
+    match (filter, value) {
+        (Filter::Number(decimals), Value::Num(n)) => Value::Str(format!("{:.*}", *decimals, n)),
+        (Filter::Bytes, Value::Num(n)) => Value::Str(format_bytes(n)),
+        (Filter::Duration, Value::Num(n)) => {
+            Value::Str(format_duration(chrono::Duration::seconds(n as i64)))
+        }
+        (Filter::Upper, v) => Value::Str(v.into_string().to_uppercase()),
+        (Filter::Lower, v) => Value::Str(v.into_string().to_lowercase()),
+        (Filter::Default(fallback), v) if !v.is_truthy() => Value::Str(fallback.clone()),
+        (_, v) => v,
+    }
+}
+
+/// Up to two decimals, without trailing zeros
+fn format_number(n: f64) -> String {
+    let s = format!("{:.2}", n);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn format_bytes(n: f64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = n;
+    let mut unit = 0;
+    while value.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", n)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Runbook URLs keyed by metric name, metric family or `default`
+#[derive(Debug, Default)]
+pub struct Runbooks {
+    urls: HashMap<String, Template>,
+}
+
+impl Runbooks {
+    pub fn url_for(&self, alert: &Alert) -> Option<String> {
+        let url = self.urls.get(&alert.metric.to_string())
+            .or_else(|| self.urls.get(&metric_family(alert)))
+            .or_else(|| self.urls.get("default"))?;
+        Some(url.render(alert, no_escape))
+    }
+}
+
+/// Templates from `[alerting.templates]`, resolved per channel
+pub struct Templates {
+    templates: HashMap<String, Template>,
+    /// Webhook `body_template`s by instance name
+    bodies: HashMap<String, Template>,
+    runbooks: Arc<Runbooks>,
+}
+
+impl Templates {
+    /// `channels` are the enabled channel instance names
+    pub fn new(config: &AlertingConfig, channels: &[String]) -> Result<Self> {
+        let urls = config.runbooks.iter()
+            .map(|(key, url)| {
+                let template = Template::parse(url)
+                    .with_context(|| format!("Invalid runbook URL for {}", key))?;
+                Ok((key.clone(), template))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let runbooks = Arc::new(Runbooks { urls });
+
+        let templates = config.templates.iter()
+            .map(|(key, source)| {
+                if key != "default" && !CHANNEL_TYPES.contains(&key.as_str()) && !channels.contains(key) {
+                    bail!("Unknown channel `{}` in alerting.templates", key);
+                }
+                let template = Template::parse_with(source, runbooks.clone())
+                    .with_context(|| format!("Invalid template `{}`", key))?;
+                Ok((key.clone(), template))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let bodies = config.webhook.iter()
+            .filter(|c| c.enabled)
+            .filter_map(|c| c.body_template.as_ref().map(|source| (c, source)))
+            .map(|(c, source)| {
+                let template = Template::parse_with(source, runbooks.clone())
+                    .with_context(|| format!("Invalid body_template of webhook `{}`", c.name))?;
+                Ok((c.name.clone(), template))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(Self { templates, bodies, runbooks })
+    }
+
+    /// The template for a channel instance, else for its type, else the
+    /// default; `None` keeps the channel's built-in format
+    pub fn for_channel(&self, name: &str, kind: &str) -> Option<Template> {
+        self.templates.get(name)
+            .or_else(|| self.templates.get(kind))
+            .or_else(|| self.templates.get("default"))
+            .cloned()
+    }
+
+    /// The request body template of a webhook instance
+    pub fn body_for(&self, name: &str) -> Option<Template> {
+        self.bodies.get(name).cloned()
+    }
+
+    /// Runbook URLs for channels that link them outside the message text
+    pub fn runbooks(&self) -> Arc<Runbooks> {
+        self.runbooks.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Label, MetricId, Severity};
+    use smallvec::smallvec;
+
+    fn alert() -> Alert {
+        Alert {
+            timestamp: chrono::Utc::now(),
+            severity: Severity::Critical,
+            metric: MetricId::DiskUsage,
+            value: 93.456,
+            threshold: Some(90.0),
+            message: "Disk usage 93.5% on /data".into(),
+            labels: smallvec![Label { key: "mountpoint".into(), value: "/data".into() }],
+            hostname: "db-01".into(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
+        }
+    }
+
+    fn render(source: &str, alert: &Alert) -> String {
+        Template::parse(source).unwrap().render(alert, no_escape)
+    }
+
+    fn angle_escape(s: &str) -> String {
+        s.replace('<', "&lt;")
+    }
+
+    #[test]
+    fn substitutes_variables_through_filters() {
+        let alert = alert();
+
+        assert_eq!(render("{{hostname}} {{severity | lower}} {{value | number:1}}/{{threshold}}", &alert), "db-01 critical 93.5/90");
+        assert_eq!(render("{{labels.mountpoint}} {{labels}}", &alert), "/data mountpoint=/data");
+        assert_eq!(render("{{escalated_from | default:\"none\"}} {{labels.device}}", &alert), "none ");
+    }
+
+    #[test]
+    fn formats_bytes_and_durations() {
+        let mut alert = alert();
+        alert.value = 1536.0 * 1024.0 * 1024.0;
+        assert_eq!(render("{{value | bytes}}", &alert), "1.5 GiB");
+        alert.value = 3903.0;
+        assert_eq!(render("{{value | duration}}", &alert), "1h 5m 3s");
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let source = "{{#if resolved}}OK{{else}}{{#if severity == \"critical\"}}PAGE{{else}}warn{{/if}}{{/if}}";
+        let mut alert = alert();
+        assert_eq!(render(source, &alert), "PAGE");
+        alert.severity = Severity::Warn;
+        assert_eq!(render(source, &alert), "warn");
+        alert.status = AlertStatus::Resolved;
+        assert_eq!(render(source, &alert), "OK");
+        assert_eq!(render("{{#if source != \"log\"}}metric{{/if}}", &alert), "metric");
+    }
+
+    #[test]
+    fn escapes_substituted_values_only() {
+        let mut alert = alert();
+        alert.message = "<script>".into();
+
+        let rendered = Template::parse("<b>{{message}}</b>").unwrap().render(&alert, angle_escape);
+
+        assert_eq!(rendered, "<b>&lt;script></b>");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for source in ["{{hostnme}}", "{{value | round}}", "{{#if resolved}}x", "{{/if}}", "{{value", "{{#if status}}a{{else}}b{{else}}c{{/if}}"] {
+            assert!(Template::parse(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn resolves_templates_and_runbooks_per_channel() {
+        let config: AlertingConfig = toml::from_str(
+            r#"
+            [templates]
+            default = "default"
+            slack = "slack"
+            ops-slack = "instance"
+            [runbooks]
+            disk = "https://runbooks.example.com/{{metric}}?host={{hostname}}"
+            "#,
+        ).unwrap();
+        let templates = Templates::new(&config, &["ops-slack".into()]).unwrap();
+        let alert = alert();
+
+        let render_for = |name: &str, kind: &str| templates.for_channel(name, kind).unwrap().render(&alert, no_escape);
+        assert_eq!(render_for("ops-slack", "slack"), "instance");
+        assert_eq!(render_for("team-slack", "slack"), "slack");
+        assert_eq!(render_for("pager", "pagerduty"), "default");
+        assert_eq!(
+            templates.runbooks().url_for(&alert).as_deref(),
+            Some("https://runbooks.example.com/disk.usage_percent?host=db-01"),
+        );
+        assert!(Templates::new(&config, &[]).is_err());
+    }
+}
@@ -1,21 +1,45 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use hmac::{Hmac, Mac};
 use serde_json::json;
+use sha2::Sha256;
 
-use crate::config::WebhookConfig;
-use crate::types::{Alert, Severity};
-use super::AlertChannel;
+use crate::config::{BasicAuthConfig, WebhookConfig};
+use crate::types::{Alert, AlertStatus, Severity};
+use super::delivery::{check_status, InvalidPayload};
+use super::template::{no_escape, Template};
+use super::{group_summary, http_client_builder, AlertChannel};
 
 /// Custom webhook alert channel
 pub struct WebhookChannel {
+    name: String,
     url: String,
+    method: reqwest::Method,
     headers: std::collections::HashMap<String, String>,
     severity_filter: Vec<Severity>,
+    template: Option<Template>,
+    /// Replaces the built-in JSON payload
+    body_template: Option<Template>,
+    signing: Option<Signing>,
+    auth: Option<Auth>,
     client: reqwest::Client,
 }
 
+/// HMAC-SHA256 request signing: the signature header carries
+/// `sha256=<hex>` over `<timestamp>.<body>`
+struct Signing {
+    secret: String,
+    signature_header: String,
+    timestamp_header: String,
+}
+
+enum Auth {
+    Bearer(String),
+    Basic(BasicAuthConfig),
+}
+
 impl WebhookChannel {
-    pub fn new(config: &WebhookConfig) -> Result<Self> {
+    pub fn new(config: &WebhookConfig, template: Option<Template>, body_template: Option<Template>) -> Result<Self> {
         let severity_filter = config.severity_filter.iter()
             .filter_map(|s| match s.as_str() {
                 "info" => Some(Severity::Info),
@@ -26,39 +50,226 @@ impl WebhookChannel {
             })
             .collect();
 
+        let method = reqwest::Method::from_bytes(config.method.trim().to_uppercase().as_bytes())
+            .with_context(|| format!("Invalid method `{}` for webhook `{}`", config.method, config.name))?;
+
+        let signing = config.signing_secret.as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|secret| Signing {
+                secret: secret.clone(),
+                signature_header: config.signature_header.clone(),
+                timestamp_header: config.timestamp_header.clone(),
+            });
+
+        // An unset `${VAR}` leaves an empty token; treat it as no auth
+        let bearer = config.bearer_token.as_ref().filter(|t| !t.is_empty());
+        let auth = match (bearer, &config.basic_auth) {
+            (Some(_), Some(_)) => bail!("Webhook `{}` sets both bearer_token and basic_auth", config.name),
+            (Some(token), None) => Some(Auth::Bearer(token.clone())),
+            (None, Some(basic)) => Some(Auth::Basic(basic.clone())),
+            (None, None) => None,
+        };
+
         Ok(Self {
+            name: config.name.clone(),
             url: config.url.clone(),
+            method,
             headers: config.headers.clone(),
             severity_filter,
-            client: reqwest::Client::new(),
+            template,
+            body_template,
+            signing,
+            auth,
+            client: build_client(config)?,
         })
     }
 }
 
-#[async_trait]
-impl AlertChannel for WebhookChannel {
-    fn name(&self) -> &str { "webhook" }
+fn build_client(config: &WebhookConfig) -> Result<reqwest::Client> {
+    let builder = configure_tls(http_client_builder(config.timeout_secs), config)?;
+    Ok(builder.build()?)
+}
 
-    fn accepts_severity(&self, severity: &Severity) -> bool {
-        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+/// Trust `tls_ca_file` and present `tls_cert_file`/`tls_key_file`
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+fn configure_tls(mut builder: reqwest::ClientBuilder, config: &WebhookConfig) -> Result<reqwest::ClientBuilder> {
+    if let Some(ref path) = config.tls_ca_file {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read tls_ca_file {} of webhook `{}`", path, config.name))?;
+        let certs = reqwest::Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid CA bundle {}", path))?;
+        if certs.is_empty() {
+            bail!("No certificates in CA bundle {}", path);
+        }
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
     }
 
-    async fn send(&self, alert: &Alert) -> Result<()> {
-        let payload = json!({
+    match (&config.tls_cert_file, &config.tls_key_file) {
+        (Some(cert_path), key_path) => {
+            let cert = std::fs::read(cert_path)
+                .with_context(|| format!("Failed to read tls_cert_file {} of webhook `{}`", cert_path, config.name))?;
+            let key = match key_path {
+                Some(path) => std::fs::read(path)
+                    .with_context(|| format!("Failed to read tls_key_file {} of webhook `{}`", path, config.name))?,
+                None => Vec::new(),
+            };
+            builder = builder.identity(client_identity(cert, key)
+                .with_context(|| format!("Invalid TLS client certificate for webhook `{}`", config.name))?);
+        }
+        (None, Some(_)) => bail!("Webhook `{}` sets tls_key_file without tls_cert_file", config.name),
+        (None, None) => {}
+    }
+
+    Ok(builder)
+}
+
+#[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+fn configure_tls(builder: reqwest::ClientBuilder, config: &WebhookConfig) -> Result<reqwest::ClientBuilder> {
+    if config.tls_ca_file.is_some() || config.tls_cert_file.is_some() || config.tls_key_file.is_some() {
+        bail!(
+            "Webhook `{}` sets TLS certificate files, but the agent was built without TLS support \
+             (enable the tls-rustls or tls-native feature)",
+            config.name
+        );
+    }
+    Ok(builder)
+}
+
+/// Client certificate and PKCS#8 key, from one PEM file or two
+#[cfg(feature = "tls-rustls")]
+fn client_identity(mut cert: Vec<u8>, key: Vec<u8>) -> Result<reqwest::Identity> {
+    cert.push(b'\n');
+    cert.extend_from_slice(&key);
+    Ok(reqwest::Identity::from_pem(&cert)?)
+}
+
+#[cfg(all(feature = "tls-native", not(feature = "tls-rustls")))]
+fn client_identity(cert: Vec<u8>, key: Vec<u8>) -> Result<reqwest::Identity> {
+    // native-tls wants the key on its own; it may follow the certificate
+    let key = if key.is_empty() { cert.clone() } else { key };
+    Ok(reqwest::Identity::from_pkcs8_pem(&cert, &key)?)
+}
+
+impl WebhookChannel {
+    fn payload(&self, alert: &Alert) -> serde_json::Value {
+        let labels: serde_json::Map<String, serde_json::Value> = alert.labels.iter()
+            .map(|l| (l.key.clone(), json!(l.value)))
+            .collect();
+        let mut payload = json!({
             "hostname": &alert.hostname,
             "metric": alert.metric.to_string(),
             "value": alert.value,
             "severity": alert.severity.to_string(),
+            "status": alert.status,
+            "source": &alert.source,
             "message": &alert.message,
             "timestamp": alert.timestamp.to_rfc3339(),
+            "labels": labels,
         });
+        if let Some(threshold) = alert.threshold {
+            payload["threshold"] = json!(threshold);
+        }
+        if let Some(started) = alert.started_at {
+            payload["started_at"] = json!(started.to_rfc3339());
+        }
+        if let Some(original) = alert.escalated_from {
+            payload["escalated"] = json!(true);
+            payload["escalated_from"] = json!(original.to_string());
+        }
+        if alert.status == AlertStatus::Resolved {
+            if let Some(d) = alert.active_duration() {
+                payload["duration_secs"] = json!(d.num_seconds());
+            }
+        }
+        if let Some(ref template) = self.template {
+            payload["text"] = json!(template.render(alert, no_escape));
+        }
+        payload
+    }
 
-        let mut req = self.client.post(&self.url).json(&payload);
+    /// The body template rendered for one alert, checked to be valid JSON
+    fn render_body(&self, template: &Template, alert: &Alert) -> Result<Vec<u8>> {
+        let body = template.render(alert, escape_json);
+        if let Err(e) = serde_json::from_str::<serde::de::IgnoredAny>(&body) {
+            return Err(InvalidPayload(format!("body_template rendered invalid JSON: {}", e)).into());
+        }
+        Ok(body.into_bytes())
+    }
+
+    async fn post(&self, body: Vec<u8>) -> Result<()> {
+        let mut req = self.client.request(self.method.clone(), &self.url);
+        if !self.headers.keys().any(|k| k.eq_ignore_ascii_case("content-type")) {
+            req = req.header(reqwest::header::CONTENT_TYPE, "application/json");
+        }
         for (k, v) in &self.headers {
             req = req.header(k, v);
         }
+        req = match self.auth {
+            Some(Auth::Bearer(ref token)) => req.bearer_auth(token),
+            Some(Auth::Basic(ref basic)) => req.basic_auth(&basic.username, basic.password.as_ref()),
+            None => req,
+        };
+        // Signed on every attempt, so a retry carries a fresh timestamp
+        if let Some(ref signing) = self.signing {
+            let timestamp = chrono::Utc::now().timestamp();
+            req = req
+                .header(&signing.timestamp_header, timestamp.to_string())
+                .header(&signing.signature_header, signing.sign(timestamp, &body));
+        }
 
-        req.send().await?.error_for_status()?;
+        check_status(req.body(body).send().await?)?;
         Ok(())
     }
 }
+
+impl Signing {
+    fn sign(&self, timestamp: i64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+/// Substituted values land inside JSON strings; numbers render unquoted
+/// and need no escaping
+fn escape_json(s: &str) -> String {
+    let quoted = serde_json::Value::String(s.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[async_trait]
+impl AlertChannel for WebhookChannel {
+    fn name(&self) -> &str { &self.name }
+
+    fn accepts_severity(&self, severity: &Severity) -> bool {
+        self.severity_filter.is_empty() || self.severity_filter.contains(severity)
+    }
+
+    fn sends_groups(&self) -> bool {
+        // A body template describes one alert
+        self.body_template.is_none()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let body = match self.body_template {
+            Some(ref template) => self.render_body(template, alert)?,
+            None => serde_json::to_vec(&self.payload(alert))?,
+        };
+        self.post(body).await
+    }
+
+    async fn send_batch(&self, alerts: &[Alert]) -> Result<()> {
+        let payload = json!({
+            "hostname": alerts.first().map(|a| a.hostname.as_str()).unwrap_or_default(),
+            "summary": group_summary(alerts),
+            "count": alerts.len(),
+            "alerts": alerts.iter().map(|a| self.payload(a)).collect::<Vec<_>>(),
+        });
+        self.post(serde_json::to_vec(&payload)?).await
+    }
+}
@@ -22,11 +22,11 @@ pub trait Analyzer: Send + Sync {
 
 /// Create all configured analyzers
 pub fn create_analyzers(config: &Config) -> Result<Vec<Box<dyn Analyzer>>> {
-    let mut analyzers: Vec<Box<dyn Analyzer>> = Vec::new();
-
-    analyzers.push(Box::new(threshold::ThresholdAnalyzer::new(&config.thresholds)));
-    analyzers.push(Box::new(zscore::ZScoreAnalyzer::new(&config.analyzer)));
-    analyzers.push(Box::new(trend::TrendAnalyzer::new(&config.analyzer)));
+    let analyzers: Vec<Box<dyn Analyzer>> = vec![
+        Box::new(threshold::ThresholdAnalyzer::new(&config.thresholds)),
+        Box::new(zscore::ZScoreAnalyzer::new(&config.analyzer)),
+        Box::new(trend::TrendAnalyzer::new(&config.analyzer)),
+    ];
 
     tracing::info!(count = analyzers.len(), "Initialized analyzers");
     Ok(analyzers)
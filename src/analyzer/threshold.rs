@@ -1,6 +1,6 @@
 use crate::config::ThresholdConfig;
-use crate::storage::Storage;
-use crate::types::{Alert, MetricId, Severity};
+use crate::storage::{SeriesKey, Storage};
+use crate::types::{Alert, AlertStatus, MetricId, Severity};
 use super::Analyzer;
 
 /// Simple threshold-based anomaly detection.
@@ -21,8 +21,8 @@ impl ThresholdAnalyzer {
         }
     }
 
-    fn check_metric(&self, storage: &Storage, metric: MetricId, warn: f64, crit: f64) -> Option<Alert> {
-        let latest = storage.latest(metric)?;
+    fn check_series(&self, storage: &Storage, key: &SeriesKey, warn: f64, crit: f64) -> Option<Alert> {
+        let latest = storage.latest(key)?;
 
         let (severity, threshold) = if latest.value >= crit {
             (Severity::Critical, crit)
@@ -35,14 +35,26 @@ impl ThresholdAnalyzer {
         Some(Alert {
             timestamp: latest.timestamp,
             severity,
-            metric,
+            metric: key.metric,
             value: latest.value,
             threshold: Some(threshold),
-            message: format!("{} is {:.1}% (threshold: {:.1}%)", metric, latest.value, threshold),
-            labels: latest.labels.clone(),
+            message: format!("{} is {:.1}% (threshold: {:.1}%)", key, latest.value, threshold),
+            labels: key.labels.clone(),
             hostname: self.hostname.clone(),
+            source: "threshold".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
         })
     }
+
+    /// Check every series of a metric independently
+    fn check_metric(&self, storage: &Storage, metric: MetricId, warn: f64, crit: f64) -> Vec<Alert> {
+        storage.series(metric)
+            .iter()
+            .filter_map(|key| self.check_series(storage, key, warn, crit))
+            .collect()
+    }
 }
 
 impl Analyzer for ThresholdAnalyzer {
@@ -51,22 +63,14 @@ impl Analyzer for ThresholdAnalyzer {
     fn analyze(&mut self, storage: &Storage) -> Vec<Alert> {
         let mut alerts = Vec::new();
 
-        if let Some(a) = self.check_metric(storage, MetricId::CpuUsage,
-            self.thresholds.cpu_warn_percent, self.thresholds.cpu_critical_percent) {
-            alerts.push(a);
-        }
-        if let Some(a) = self.check_metric(storage, MetricId::MemUsage,
-            self.thresholds.memory_warn_percent, self.thresholds.memory_critical_percent) {
-            alerts.push(a);
-        }
-        if let Some(a) = self.check_metric(storage, MetricId::DiskUsage,
-            self.thresholds.disk_warn_percent, self.thresholds.disk_critical_percent) {
-            alerts.push(a);
-        }
-        if let Some(a) = self.check_metric(storage, MetricId::FdSystemUsage,
-            self.thresholds.fd_warn_percent, self.thresholds.fd_critical_percent) {
-            alerts.push(a);
-        }
+        alerts.extend(self.check_metric(storage, MetricId::CpuUsage,
+            self.thresholds.cpu_warn_percent, self.thresholds.cpu_critical_percent));
+        alerts.extend(self.check_metric(storage, MetricId::MemUsage,
+            self.thresholds.memory_warn_percent, self.thresholds.memory_critical_percent));
+        alerts.extend(self.check_metric(storage, MetricId::DiskUsage,
+            self.thresholds.disk_warn_percent, self.thresholds.disk_critical_percent));
+        alerts.extend(self.check_metric(storage, MetricId::FdSystemUsage,
+            self.thresholds.fd_warn_percent, self.thresholds.fd_critical_percent));
 
         alerts
     }
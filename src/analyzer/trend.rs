@@ -1,6 +1,6 @@
 use crate::config::AnalyzerConfig;
-use crate::storage::Storage;
-use crate::types::{Alert, MetricId, Severity};
+use crate::storage::{SeriesKey, Storage};
+use crate::types::{Alert, AlertStatus, MetricId, Severity};
 use super::Analyzer;
 
 /// Trend-based anomaly detection using linear regression.
@@ -31,7 +31,6 @@ impl TrendAnalyzer {
         let sum_y: f64 = ys.iter().sum();
         let sum_xy: f64 = xs.iter().zip(ys).map(|(x, y)| x * y).sum();
         let sum_x2: f64 = xs.iter().map(|x| x * x).sum();
-        let sum_y2: f64 = ys.iter().map(|y| y * y).sum();
 
         let denom = n * sum_x2 - sum_x * sum_x;
         if denom.abs() < 1e-10 { return None; }
@@ -51,9 +50,9 @@ impl TrendAnalyzer {
         Some((slope, intercept, r_squared))
     }
 
-    fn check_exhaustion(&self, storage: &Storage, metric: MetricId, limit: f64, hours_warn: f64, hours_crit: f64) -> Option<Alert> {
+    fn check_series(&self, storage: &Storage, key: &SeriesKey, limit: f64, hours_warn: f64, hours_crit: f64) -> Option<Alert> {
         let samples_needed = (self.window_hours as usize) * 360; // 10s intervals
-        let samples = storage.recent(metric, samples_needed);
+        let samples = storage.recent(key, samples_needed);
         if samples.len() < 60 { return None; } // At least 10 minutes of data
 
         let xs: Vec<f64> = samples.iter()
@@ -83,17 +82,29 @@ impl TrendAnalyzer {
         Some(Alert {
             timestamp: samples.last()?.timestamp,
             severity,
-            metric,
+            metric: key.metric,
             value: current,
             threshold: Some(limit),
             message: format!(
                 "{} trending toward exhaustion: {:.1}h remaining (current={:.1}%, slope={:.4}/s, R²={:.2})",
-                metric, hours_to_exhaustion, current, slope, r_squared
+                key, hours_to_exhaustion, current, slope, r_squared
             ),
-            labels: samples.last()?.labels.clone(),
+            labels: key.labels.clone(),
             hostname: self.hostname.clone(),
+            source: "trend".into(),
+            status: AlertStatus::Firing,
+            started_at: None,
+            escalated_from: None,
         })
     }
+
+    /// Check every series of a metric independently
+    fn check_exhaustion(&self, storage: &Storage, metric: MetricId, limit: f64, hours_warn: f64, hours_crit: f64) -> Vec<Alert> {
+        storage.series(metric)
+            .iter()
+            .filter_map(|key| self.check_series(storage, key, limit, hours_warn, hours_crit))
+            .collect()
+    }
 }
 
 impl Analyzer for TrendAnalyzer {
@@ -103,19 +114,13 @@ impl Analyzer for TrendAnalyzer {
         let mut alerts = Vec::new();
 
         // Disk: warn at 72h, critical at 24h
-        if let Some(a) = self.check_exhaustion(storage, MetricId::DiskUsage, 100.0, 72.0, 24.0) {
-            alerts.push(a);
-        }
+        alerts.extend(self.check_exhaustion(storage, MetricId::DiskUsage, 100.0, 72.0, 24.0));
 
         // Memory: warn at 12h, critical at 6h
-        if let Some(a) = self.check_exhaustion(storage, MetricId::MemUsage, 100.0, 12.0, 6.0) {
-            alerts.push(a);
-        }
+        alerts.extend(self.check_exhaustion(storage, MetricId::MemUsage, 100.0, 12.0, 6.0));
 
         // FD: warn at 24h, critical at 6h
-        if let Some(a) = self.check_exhaustion(storage, MetricId::FdSystemUsage, 100.0, 24.0, 6.0) {
-            alerts.push(a);
-        }
+        alerts.extend(self.check_exhaustion(storage, MetricId::FdSystemUsage, 100.0, 24.0, 6.0));
 
         alerts
     }
@@ -1,6 +1,6 @@
 use crate::config::AnalyzerConfig;
-use crate::storage::Storage;
-use crate::types::{Alert, MetricId, Severity};
+use crate::storage::{SeriesKey, Storage};
+use crate::types::{Alert, AlertStatus, MetricId, Severity};
 use super::Analyzer;
 
 /// Z-Score based anomaly detection.
@@ -23,8 +23,8 @@ impl ZScoreAnalyzer {
         }
     }
 
-    fn check_metric(&self, storage: &Storage, metric: MetricId) -> Option<Alert> {
-        let samples = storage.recent(metric, self.window_size as usize);
+    fn check_series(&self, storage: &Storage, key: &SeriesKey) -> Option<Alert> {
+        let samples = storage.recent(key, self.window_size as usize);
         if samples.len() < 30 {
             return None; // Not enough data
         }
@@ -52,15 +52,19 @@ impl ZScoreAnalyzer {
             Some(Alert {
                 timestamp: samples.last()?.timestamp,
                 severity,
-                metric,
+                metric: key.metric,
                 value: *latest,
                 threshold: None,
                 message: format!(
                     "{} anomaly detected: z-score={:.2} (value={:.1}, mean={:.1}, stddev={:.1})",
-                    metric, z, latest, mean, stddev
+                    key, z, latest, mean, stddev
                 ),
-                labels: samples.last()?.labels.clone(),
+                labels: key.labels.clone(),
                 hostname: self.hostname.clone(),
+                source: "zscore".into(),
+                status: AlertStatus::Firing,
+                started_at: None,
+                escalated_from: None,
             })
         } else {
             None
@@ -81,7 +85,8 @@ impl Analyzer for ZScoreAnalyzer {
         ];
 
         metrics.iter()
-            .filter_map(|m| self.check_metric(storage, *m))
+            .flat_map(|m| storage.series(*m))
+            .filter_map(|key| self.check_series(storage, &key))
             .collect()
     }
 }
@@ -1,17 +1,45 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::Utc;
-use smallvec::smallvec;
+use smallvec::{smallvec, SmallVec};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::config::DiskCollectorConfig;
 use crate::types::{Label, MetricId, MetricSample};
 use super::Collector;
 
+/// Upper bound on a single statvfs() call; a hard-mounted NFS share whose
+/// server went away would otherwise block the collector forever.
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Collects disk usage and I/O metrics
 pub struct DiskCollector {
     interval: u64,
     exclude_fstypes: Vec<String>,
     exclude_mountpoints: Vec<String>,
+    /// Mountpoints whose statvfs() call has not returned yet
+    pending: Arc<Mutex<HashSet<String>>>,
+}
+
+/// One filesystem entry from /proc/self/mountinfo
+#[derive(Debug, Clone)]
+struct MountEntry {
+    device_id: String,
+    root: String,
+    mountpoint: String,
+    fstype: String,
+    source: String,
+}
+
+/// Capacity figures for one filesystem, in bytes / inodes
+struct FsUsage {
+    total: u64,
+    free: u64,
+    available: u64,
+    files: u64,
+    files_free: u64,
 }
 
 impl DiskCollector {
@@ -20,10 +48,112 @@ impl DiskCollector {
             interval: config.interval_secs,
             exclude_fstypes: config.exclude_fstypes.clone(),
             exclude_mountpoints: config.exclude_mountpoints.clone(),
+            pending: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// Parse /proc/self/mountinfo, apply the exclude filters and collapse
+    /// bind mounts / duplicate devices so each filesystem appears once.
+    fn parse_mountinfo(&self, content: &str) -> Vec<MountEntry> {
+        let mut by_device: HashMap<String, usize> = HashMap::new();
+        let mut mounts: Vec<MountEntry> = Vec::new();
+
+        for line in content.lines() {
+            // Format: id parent major:minor root mountpoint opts [optional...] - fstype source superopts
+            let Some((left, right)) = line.split_once(" - ") else { continue };
+            let left: Vec<&str> = left.split_whitespace().collect();
+            let right: Vec<&str> = right.split_whitespace().collect();
+            if left.len() < 5 || right.len() < 2 { continue; }
+
+            let entry = MountEntry {
+                device_id: left[2].to_string(),
+                root: unescape_mount_field(left[3]),
+                mountpoint: unescape_mount_field(left[4]),
+                fstype: right[0].to_string(),
+                source: unescape_mount_field(right[1]),
+            };
+
+            if self.exclude_fstypes.contains(&entry.fstype) { continue; }
+            if self.exclude_mountpoints.contains(&entry.mountpoint) { continue; }
+
+            match by_device.get(&entry.device_id) {
+                // Prefer the mount of the filesystem root over bind mounts of a subtree
+                Some(&idx) => {
+                    if mounts[idx].root != "/" && entry.root == "/" {
+                        mounts[idx] = entry;
+                    }
+                }
+                None => {
+                    by_device.insert(entry.device_id.clone(), mounts.len());
+                    mounts.push(entry);
+                }
+            }
+        }
+
+        mounts
+    }
+
+    /// Run statvfs() on a blocking thread with a timeout. Mountpoints whose
+    /// previous call is still stuck are skipped rather than piling up threads.
+    async fn statvfs(&self, mountpoint: &str) -> Option<FsUsage> {
+        if !self.pending.lock().unwrap().insert(mountpoint.to_string()) {
+            tracing::debug!(mountpoint, "Previous statvfs still pending, skipping");
+            return None;
+        }
+
+        let pending = self.pending.clone();
+        let path = mountpoint.to_string();
+        let handle = tokio::task::spawn_blocking(move || {
+            let result = nix::sys::statvfs::statvfs(path.as_str());
+            pending.lock().unwrap().remove(&path);
+            result
+        });
+
+        let stat = match tokio::time::timeout(STATVFS_TIMEOUT, handle).await {
+            Ok(Ok(Ok(stat))) => stat,
+            Ok(Ok(Err(e))) => {
+                tracing::debug!(mountpoint, error = %e, "statvfs failed");
+                return None;
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(mountpoint, error = %e, "statvfs task failed");
+                return None;
+            }
+            Err(_) => {
+                tracing::warn!(mountpoint, "statvfs timed out, filesystem may be unresponsive");
+                return None;
+            }
+        };
+
+        let frsize = stat.fragment_size() as u64;
+        Some(FsUsage {
+            total: stat.blocks() as u64 * frsize,
+            free: stat.blocks_free() as u64 * frsize,
+            available: stat.blocks_available() as u64 * frsize,
+            files: stat.files() as u64,
+            files_free: stat.files_free() as u64,
         })
     }
 }
 
+/// Decode the octal escapes (`\040` for space etc.) the kernel uses in mountinfo
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let code = bytes[i + 1..i + 4].iter().fold(0u16, |acc, b| acc * 8 + (b - b'0') as u16);
+            out.push(code as u8);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
 #[async_trait]
 impl Collector for DiskCollector {
     fn name(&self) -> &str { "disk" }
@@ -32,45 +162,59 @@ impl Collector for DiskCollector {
         let now = Utc::now();
         let mut samples = Vec::new();
 
-        // Parse /proc/mounts for mounted filesystems
-        let mounts = tokio::fs::read_to_string("/proc/mounts").await?;
-        for line in mounts.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 3 { continue; }
-
-            let _device = parts[0];
-            let mountpoint = parts[1];
-            let fstype = parts[2];
+        let content = tokio::fs::read_to_string("/proc/self/mountinfo").await?;
+        for mount in self.parse_mountinfo(&content) {
+            let Some(usage) = self.statvfs(&mount.mountpoint).await else { continue };
 
-            if self.exclude_fstypes.iter().any(|f| f == fstype) { continue; }
-            if self.exclude_mountpoints.iter().any(|m| m == mountpoint) { continue; }
+            // Pseudo filesystems report zero blocks; nothing useful to alert on
+            if usage.total == 0 { continue; }
 
-            // Use statvfs to get disk usage (would use nix::sys::statvfs in real impl)
-            // Skeleton: placeholder for actual statvfs call
-            let labels = smallvec![
-                Label { key: "mountpoint".into(), value: mountpoint.to_string() },
-                Label { key: "fstype".into(), value: fstype.to_string() },
+            let labels: SmallVec<[Label; 4]> = smallvec![
+                Label { key: "mountpoint".into(), value: mount.mountpoint.clone() },
+                Label { key: "fstype".into(), value: mount.fstype.clone() },
+                Label { key: "device".into(), value: mount.source.clone() },
             ];
 
-            // TODO: Implement actual statvfs call
-            // let stat = nix::sys::statvfs::statvfs(mountpoint)?;
-            // let total = stat.blocks() * stat.fragment_size();
-            // let avail = stat.blocks_available() * stat.fragment_size();
-            // let usage_pct = 100.0 * (1.0 - avail as f64 / total as f64);
+            // Same definition as df: used / (used + available to non-root)
+            let used = usage.total.saturating_sub(usage.free);
+            let usable = used + usage.available;
+            let usage_pct = if usable > 0 { 100.0 * used as f64 / usable as f64 } else { 0.0 };
 
             samples.push(MetricSample {
                 timestamp: now,
                 metric: MetricId::DiskUsage,
-                value: 0.0, // placeholder
+                value: usage_pct,
+                labels: labels.clone(),
+            });
+            samples.push(MetricSample {
+                timestamp: now,
+                metric: MetricId::DiskTotal,
+                value: usage.total as f64,
+                labels: labels.clone(),
+            });
+            samples.push(MetricSample {
+                timestamp: now,
+                metric: MetricId::DiskFree,
+                value: usage.free as f64,
                 labels: labels.clone(),
             });
-
             samples.push(MetricSample {
                 timestamp: now,
                 metric: MetricId::DiskAvailable,
-                value: 0.0, // placeholder
-                labels,
+                value: usage.available as f64,
+                labels: labels.clone(),
             });
+
+            // Some filesystems (btrfs, vfat) don't have a fixed inode table
+            if usage.files > 0 {
+                let inode_pct = 100.0 * (1.0 - usage.files_free as f64 / usage.files as f64);
+                samples.push(MetricSample {
+                    timestamp: now,
+                    metric: MetricId::DiskInodeUsage,
+                    value: inode_pct,
+                    labels,
+                });
+            }
         }
 
         Ok(samples)
@@ -78,3 +222,33 @@ impl Collector for DiskCollector {
 
     fn interval_secs(&self) -> u64 { self.interval }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_octal_mount_fields() {
+        assert_eq!(unescape_mount_field(r"/mnt/my\040disk"), "/mnt/my disk");
+        assert_eq!(unescape_mount_field(r"/mnt/tab\011and\134slash"), "/mnt/tab\tand\\slash");
+        assert_eq!(unescape_mount_field(r"/mnt/not\08escape\04"), r"/mnt/not\08escape\04");
+    }
+
+    #[test]
+    fn collapses_bind_mounts_to_the_filesystem_root() {
+        let config = DiskCollectorConfig { exclude_fstypes: vec!["tmpfs".into()], ..Default::default() };
+        let collector = DiskCollector::new(&config).unwrap();
+        let mountinfo = "\
+36 25 8:1 /srv/data /var/lib/data rw,relatime shared:1 - ext4 /dev/sda1 rw
+37 25 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+38 25 8:16 / /mnt/backup\\040disk rw,relatime - xfs /dev/sdb rw
+39 25 0:30 / /run rw,nosuid - tmpfs tmpfs rw
+";
+
+        let mounts = collector.parse_mountinfo(mountinfo);
+
+        let points: Vec<&str> = mounts.iter().map(|m| m.mountpoint.as_str()).collect();
+        assert_eq!(points, ["/", "/mnt/backup disk"]);
+        assert_eq!(mounts[0].source, "/dev/sda1");
+    }
+}
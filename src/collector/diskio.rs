@@ -0,0 +1,235 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use smallvec::{smallvec, SmallVec};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use crate::config::DiskCollectorConfig;
+use crate::types::{Label, MetricId, MetricSample};
+use super::Collector;
+
+/// /proc/diskstats always counts in 512-byte sectors, regardless of the device
+const SECTOR_SIZE: u64 = 512;
+
+/// Collects block device I/O metrics from /proc/diskstats
+pub struct DiskIoCollector {
+    interval: u64,
+    exclude_devices: Vec<String>,
+    include_partitions: bool,
+    /// Whether each listed device is a partition, looked up in sysfs once
+    partitions: HashMap<String, bool>,
+    prev_stats: HashMap<String, DiskStats>,
+    prev_time: Option<Instant>,
+}
+
+/// Cumulative counters for one device line of /proc/diskstats
+#[derive(Debug, Clone, Copy)]
+struct DiskStats {
+    reads: u64,
+    sectors_read: u64,
+    read_ms: u64,
+    writes: u64,
+    sectors_written: u64,
+    write_ms: u64,
+    io_ms: u64,
+    weighted_io_ms: u64,
+}
+
+impl DiskStats {
+    /// Parse the counter fields following `major minor name`
+    fn parse(fields: &[&str]) -> Option<Self> {
+        if fields.len() < 11 { return None; }
+        let f = |i: usize| fields[i].parse::<u64>().ok();
+        Some(Self {
+            reads: f(0)?,
+            sectors_read: f(2)?,
+            read_ms: f(3)?,
+            writes: f(4)?,
+            sectors_written: f(6)?,
+            write_ms: f(7)?,
+            io_ms: f(9)?,
+            weighted_io_ms: f(10)?,
+        })
+    }
+}
+
+impl DiskIoCollector {
+    pub fn new(config: &DiskCollectorConfig) -> Result<Self> {
+        Ok(Self {
+            interval: config.io_interval_secs,
+            exclude_devices: config.exclude_devices.clone(),
+            include_partitions: config.include_partitions,
+            partitions: HashMap::new(),
+            prev_stats: HashMap::new(),
+            prev_time: None,
+        })
+    }
+
+    async fn is_excluded(&mut self, device: &str) -> bool {
+        if self.exclude_devices.iter().any(|p| device.starts_with(p.as_str())) {
+            return true;
+        }
+        !self.include_partitions && self.is_partition(device).await
+    }
+
+    /// Partitions carry a `partition` attribute in sysfs; whole disks don't.
+    /// Checked when a device first shows up, not on every collection.
+    async fn is_partition(&mut self, device: &str) -> bool {
+        if let Some(&partition) = self.partitions.get(device) {
+            return partition;
+        }
+        // sysfs spells the `/` of names like cciss/c0d0 as `!`
+        let path = format!("/sys/class/block/{}/partition", device.replace('/', "!"));
+        let partition = tokio::fs::try_exists(&path).await.unwrap_or(false);
+        self.partitions.insert(device.to_string(), partition);
+        partition
+    }
+
+    /// Compute rates from the delta between two snapshots of one device
+    fn device_samples(
+        device: &str,
+        prev: &DiskStats,
+        cur: &DiskStats,
+        elapsed_secs: f64,
+        now: chrono::DateTime<Utc>,
+    ) -> Vec<MetricSample> {
+        let labels: SmallVec<[Label; 4]> = smallvec![
+            Label { key: "device".into(), value: device.to_string() },
+        ];
+
+        let d_reads = cur.reads.saturating_sub(prev.reads);
+        let d_writes = cur.writes.saturating_sub(prev.writes);
+        let d_read_bytes = cur.sectors_read.saturating_sub(prev.sectors_read) * SECTOR_SIZE;
+        let d_write_bytes = cur.sectors_written.saturating_sub(prev.sectors_written) * SECTOR_SIZE;
+        let d_rw_ms = cur.read_ms.saturating_sub(prev.read_ms) + cur.write_ms.saturating_sub(prev.write_ms);
+        let d_io_ms = cur.io_ms.saturating_sub(prev.io_ms);
+        let d_weighted_ms = cur.weighted_io_ms.saturating_sub(prev.weighted_io_ms);
+        let d_ios = d_reads + d_writes;
+        let elapsed_ms = elapsed_secs * 1000.0;
+
+        // Same definitions as iostat -x
+        let await_ms = if d_ios > 0 { d_rw_ms as f64 / d_ios as f64 } else { 0.0 };
+        let svctm_ms = if d_ios > 0 { d_io_ms as f64 / d_ios as f64 } else { 0.0 };
+        let util_pct = (100.0 * d_io_ms as f64 / elapsed_ms).min(100.0);
+        let queue_depth = d_weighted_ms as f64 / elapsed_ms;
+
+        [
+            (MetricId::DiskReadRate, d_read_bytes as f64 / elapsed_secs),
+            (MetricId::DiskWriteRate, d_write_bytes as f64 / elapsed_secs),
+            (MetricId::DiskReadOps, d_reads as f64 / elapsed_secs),
+            (MetricId::DiskWriteOps, d_writes as f64 / elapsed_secs),
+            (MetricId::DiskAwait, await_ms),
+            (MetricId::DiskServiceTime, svctm_ms),
+            (MetricId::DiskQueueDepth, queue_depth),
+            (MetricId::DiskIoTime, util_pct),
+        ]
+        .into_iter()
+        .map(|(metric, value)| MetricSample {
+            timestamp: now,
+            metric,
+            value,
+            labels: labels.clone(),
+        })
+        .collect()
+    }
+}
+
+#[async_trait]
+impl Collector for DiskIoCollector {
+    fn name(&self) -> &str { "diskio" }
+
+    async fn collect(&mut self) -> Result<Vec<MetricSample>> {
+        let content = tokio::fs::read_to_string("/proc/diskstats").await?;
+        let now = Utc::now();
+        let now_instant = Instant::now();
+        let elapsed_secs = self.prev_time
+            .map(|t| now_instant.duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
+        let mut samples = Vec::new();
+        let mut current = HashMap::new();
+        let mut listed = HashSet::new();
+
+        for line in content.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 14 { continue; }
+
+            let device = parts[2];
+            listed.insert(device);
+            if self.is_excluded(device).await { continue; }
+
+            let Some(stats) = DiskStats::parse(&parts[3..]) else { continue };
+
+            if elapsed_secs > 0.0 {
+                if let Some(prev) = self.prev_stats.get(device) {
+                    samples.extend(Self::device_samples(device, prev, &stats, elapsed_secs, now));
+                }
+            }
+
+            current.insert(device.to_string(), stats);
+        }
+
+        // Replacing the map drops devices that disappeared (hot-unplug, detached loop)
+        self.prev_stats = current;
+        self.partitions.retain(|device, _| listed.contains(device.as_str()));
+        self.prev_time = Some(now_instant);
+
+        Ok(samples)
+    }
+
+    fn interval_secs(&self) -> u64 { self.interval }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(line: &str) -> DiskStats {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        DiskStats::parse(&parts[3..]).unwrap()
+    }
+
+    fn value(samples: &[MetricSample], metric: MetricId) -> f64 {
+        samples.iter().find(|s| s.metric == metric).unwrap().value
+    }
+
+    #[test]
+    fn rates_from_counter_deltas() {
+        let prev = stats("8 0 sda 1000 0 8000 500 2000 0 16000 1500 0 1000 2000 0 0 0 0");
+        // 100 reads of 4 KiB, 300 writes of 4 KiB in 2s; busy 1s
+        let cur = stats("8 0 sda 1100 0 8800 700 2300 0 18400 2300 0 2000 4000 0 0 0 0");
+
+        let samples = DiskIoCollector::device_samples("sda", &prev, &cur, 2.0, Utc::now());
+
+        assert_eq!(value(&samples, MetricId::DiskReadRate), 800.0 * 512.0 / 2.0);
+        assert_eq!(value(&samples, MetricId::DiskWriteRate), 2400.0 * 512.0 / 2.0);
+        assert_eq!(value(&samples, MetricId::DiskReadOps), 50.0);
+        assert_eq!(value(&samples, MetricId::DiskWriteOps), 150.0);
+        assert_eq!(value(&samples, MetricId::DiskAwait), 1000.0 / 400.0);
+        assert_eq!(value(&samples, MetricId::DiskServiceTime), 1000.0 / 400.0);
+        assert_eq!(value(&samples, MetricId::DiskQueueDepth), 1.0);
+        assert_eq!(value(&samples, MetricId::DiskIoTime), 50.0);
+        assert_eq!(samples[0].labels[0].value, "sda");
+    }
+
+    #[test]
+    fn counter_reset_gives_zero_rates() {
+        let prev = stats("8 0 sda 1000 0 8000 500 2000 0 16000 1500 0 1000 2000 0 0 0 0");
+        let cur = stats("8 0 sda 10 0 80 5 20 0 160 15 0 10 20 0 0 0 0");
+
+        let samples = DiskIoCollector::device_samples("sda", &prev, &cur, 2.0, Utc::now());
+
+        assert!(samples.iter().all(|s| s.value == 0.0));
+    }
+
+    #[tokio::test]
+    async fn partition_status_is_looked_up_once() {
+        let mut collector = DiskIoCollector::new(&DiskCollectorConfig::default()).unwrap();
+        collector.partitions.insert("sdz9".into(), true);
+
+        assert!(collector.is_excluded("loop0").await);
+        assert!(collector.is_excluded("sdz9").await);
+        collector.include_partitions = true;
+        assert!(!collector.is_excluded("sdz9").await);
+    }
+}
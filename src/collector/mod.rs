@@ -1,6 +1,7 @@
 pub mod cpu;
 pub mod memory;
 pub mod disk;
+pub mod diskio;
 pub mod network;
 pub mod process;
 pub mod fd;
@@ -42,6 +43,7 @@ pub fn create_collectors(config: &Config) -> Result<Vec<Box<dyn Collector>>> {
 
     if config.collector.disk.enabled {
         collectors.push(Box::new(disk::DiskCollector::new(&config.collector.disk)?));
+        collectors.push(Box::new(diskio::DiskIoCollector::new(&config.collector.disk)?));
     }
 
     if config.collector.network.enabled {
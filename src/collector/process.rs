@@ -1,27 +1,162 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::Utc;
-use smallvec::smallvec;
+use regex::Regex;
+use smallvec::{smallvec, SmallVec};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
 
 use crate::config::ProcessCollectorConfig;
 use crate::types::{Label, MetricId, MetricSample};
 use super::Collector;
 
+/// Longest cmdline kept in the `cmdline` label
+const MAX_CMDLINE_LEN: usize = 256;
+
 /// Collects per-process metrics from /proc/[pid]/
 pub struct ProcessCollector {
     interval: u64,
-    track_patterns: Vec<String>,
+    track_patterns: Vec<Regex>,
     track_top_n: u32,
+    clock_ticks: f64,
+    page_size: u64,
+    /// CPU jiffies from the previous pass, keyed by (pid, starttime) so a
+    /// recycled PID is never diffed against a different process
+    prev_cpu: HashMap<(u32, u64), u64>,
+    prev_time: Option<Instant>,
+}
+
+/// Fields of interest from /proc/[pid]/stat
+struct ProcStat {
+    pid: u32,
+    comm: String,
+    cpu_jiffies: u64,
+    starttime: u64,
+    rss_pages: u64,
+}
+
+/// A process considered for tracking in the current pass
+struct ProcInfo {
+    stat: ProcStat,
+    cmdline: String,
+    cpu_percent: Option<f64>,
+}
+
+impl ProcStat {
+    fn parse(pid: u32, content: &str) -> Option<Self> {
+        // comm may contain spaces and parentheses, so split at the last ')'
+        let open = content.find('(')?;
+        let close = content.rfind(')')?;
+        let comm = content.get(open + 1..close)?.to_string();
+        let fields: Vec<&str> = content.get(close + 2..)?.split_whitespace().collect();
+        // Indices are offset by 3 from the proc(5) field numbers (pid, comm, state)
+        if fields.len() < 22 { return None; }
+        let utime: u64 = fields[11].parse().ok()?;
+        let stime: u64 = fields[12].parse().ok()?;
+        Some(Self {
+            pid,
+            comm,
+            cpu_jiffies: utime + stime,
+            starttime: fields[19].parse().ok()?,
+            rss_pages: fields[21].parse().ok()?,
+        })
+    }
 }
 
 impl ProcessCollector {
     pub fn new(config: &ProcessCollectorConfig) -> Result<Self> {
+        let track_patterns = config.track_patterns.iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let clock_ticks = nix::unistd::sysconf(nix::unistd::SysconfVar::CLK_TCK)
+            .ok()
+            .flatten()
+            .unwrap_or(100) as f64;
+        let page_size = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+            .ok()
+            .flatten()
+            .unwrap_or(4096) as u64;
+
         Ok(Self {
             interval: config.interval_secs,
-            track_patterns: config.track_patterns.clone(),
+            track_patterns,
             track_top_n: config.track_top_n,
+            clock_ticks,
+            page_size,
+            prev_cpu: HashMap::new(),
+            prev_time: None,
         })
     }
+
+    fn matches_pattern(&self, proc: &ProcInfo) -> bool {
+        self.track_patterns.iter()
+            .any(|re| re.is_match(&proc.stat.comm) || re.is_match(&proc.cmdline))
+    }
+
+    /// Indices of processes to report: pattern matches plus top-N by RSS and by CPU
+    fn select(&self, procs: &[ProcInfo]) -> Vec<usize> {
+        let mut selected: HashSet<usize> = (0..procs.len())
+            .filter(|&i| self.matches_pattern(&procs[i]))
+            .collect();
+
+        let n = self.track_top_n as usize;
+        if n > 0 {
+            let mut by_rss: Vec<usize> = (0..procs.len())
+                .filter(|&i| procs[i].stat.rss_pages > 0)
+                .collect();
+            by_rss.sort_by(|&a, &b| procs[b].stat.rss_pages.cmp(&procs[a].stat.rss_pages));
+            selected.extend(by_rss.into_iter().take(n));
+
+            let mut by_cpu: Vec<usize> = (0..procs.len())
+                .filter(|&i| procs[i].cpu_percent.is_some_and(|c| c > 0.0))
+                .collect();
+            by_cpu.sort_by(|&a, &b| {
+                procs[b].cpu_percent.partial_cmp(&procs[a].cpu_percent)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            selected.extend(by_cpu.into_iter().take(n));
+        }
+
+        let mut selected: Vec<usize> = selected.into_iter().collect();
+        selected.sort_unstable();
+        selected
+    }
+}
+
+async fn read_cmdline(pid: u32) -> String {
+    let raw = tokio::fs::read(format!("/proc/{}/cmdline", pid)).await.unwrap_or_default();
+    let mut cmdline: String = String::from_utf8_lossy(&raw)
+        .split('\0')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if cmdline.len() > MAX_CMDLINE_LEN {
+        let mut end = MAX_CMDLINE_LEN;
+        while !cmdline.is_char_boundary(end) { end -= 1; }
+        cmdline.truncate(end);
+    }
+    cmdline
+}
+
+/// VmRSS from /proc/[pid]/status, in bytes
+async fn read_status_rss(pid: u32) -> Option<u64> {
+    let content = tokio::fs::read_to_string(format!("/proc/{}/status", pid)).await.ok()?;
+    content.lines()
+        .find(|l| l.starts_with("VmRSS:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+/// Number of open descriptors; requires the same UID or CAP_SYS_PTRACE
+async fn count_fds(pid: u32) -> Option<u64> {
+    let mut entries = tokio::fs::read_dir(format!("/proc/{}/fd", pid)).await.ok()?;
+    let mut count = 0u64;
+    while let Ok(Some(_)) = entries.next_entry().await {
+        count += 1;
+    }
+    Some(count)
 }
 
 #[async_trait]
@@ -30,35 +165,148 @@ impl Collector for ProcessCollector {
 
     async fn collect(&mut self) -> Result<Vec<MetricSample>> {
         let now = Utc::now();
+        let now_instant = Instant::now();
+        let elapsed_secs = self.prev_time
+            .map(|t| now_instant.duration_since(t).as_secs_f64())
+            .unwrap_or(0.0);
         let mut samples = Vec::new();
 
-        // Count total processes
-        let mut proc_count = 0u64;
+        let mut pids = Vec::new();
         let mut entries = tokio::fs::read_dir("/proc").await?;
         while let Some(entry) = entries.next_entry().await? {
-            if entry.file_name().to_str()
-                .map(|s| s.chars().all(|c| c.is_ascii_digit()))
-                .unwrap_or(false)
-            {
-                proc_count += 1;
+            if let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) {
+                pids.push(pid);
             }
         }
 
         samples.push(MetricSample {
             timestamp: now,
             metric: MetricId::ProcCount,
-            value: proc_count as f64,
+            value: pids.len() as f64,
             labels: smallvec![],
         });
 
-        // TODO: Implement per-process tracking
-        // - Read /proc/[pid]/status for VmRSS, Threads
-        // - Read /proc/[pid]/stat for CPU time
-        // - Filter by track_patterns or top-N by RSS
-        // - Track RSS over time for leak detection
+        // Processes may exit between readdir and here; missing files are skipped
+        let mut procs = Vec::with_capacity(pids.len());
+        let mut current_cpu = HashMap::with_capacity(pids.len());
+        for pid in pids {
+            let Ok(content) = tokio::fs::read_to_string(format!("/proc/{}/stat", pid)).await else { continue };
+            let Some(stat) = ProcStat::parse(pid, &content) else { continue };
+
+            let key = (stat.pid, stat.starttime);
+            let cpu_percent = match self.prev_cpu.get(&key) {
+                Some(&prev) if elapsed_secs > 0.0 => {
+                    let d_jiffies = stat.cpu_jiffies.saturating_sub(prev) as f64;
+                    Some(100.0 * d_jiffies / self.clock_ticks / elapsed_secs)
+                }
+                _ => None,
+            };
+            current_cpu.insert(key, stat.cpu_jiffies);
+
+            let cmdline = if self.track_patterns.is_empty() {
+                String::new()
+            } else {
+                read_cmdline(pid).await
+            };
+
+            procs.push(ProcInfo { stat, cmdline, cpu_percent });
+        }
+
+        for idx in self.select(&procs) {
+            let proc = &mut procs[idx];
+            let pid = proc.stat.pid;
+            if proc.cmdline.is_empty() {
+                proc.cmdline = read_cmdline(pid).await;
+            }
+
+            let labels: SmallVec<[Label; 4]> = smallvec![
+                Label { key: "pid".into(), value: pid.to_string() },
+                Label { key: "name".into(), value: proc.stat.comm.clone() },
+                Label { key: "cmdline".into(), value: proc.cmdline.clone() },
+            ];
+
+            let rss = read_status_rss(pid).await
+                .unwrap_or(proc.stat.rss_pages * self.page_size);
+            samples.push(MetricSample {
+                timestamp: now,
+                metric: MetricId::ProcRss,
+                value: rss as f64,
+                labels: labels.clone(),
+            });
+
+            if let Some(cpu) = proc.cpu_percent {
+                samples.push(MetricSample {
+                    timestamp: now,
+                    metric: MetricId::ProcCpu,
+                    value: cpu,
+                    labels: labels.clone(),
+                });
+            }
+
+            if let Some(fds) = count_fds(pid).await {
+                samples.push(MetricSample {
+                    timestamp: now,
+                    metric: MetricId::ProcFdCount,
+                    value: fds as f64,
+                    labels,
+                });
+            }
+        }
+
+        self.prev_cpu = current_cpu;
+        self.prev_time = Some(now_instant);
 
         Ok(samples)
     }
 
     fn interval_secs(&self) -> u64 { self.interval }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proc(pid: u32, comm: &str, rss_pages: u64, cpu_percent: Option<f64>) -> ProcInfo {
+        ProcInfo {
+            stat: ProcStat { pid, comm: comm.into(), cpu_jiffies: 0, starttime: 0, rss_pages },
+            cmdline: format!("/usr/bin/{}", comm),
+            cpu_percent,
+        }
+    }
+
+    #[test]
+    fn parses_stat_with_parentheses_in_comm() {
+        let content = "1234 (my (odd) proc) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 1 0 5000 12345678 789 18446744073709551615\n";
+
+        let stat = ProcStat::parse(1234, content).unwrap();
+
+        assert_eq!(stat.comm, "my (odd) proc");
+        assert_eq!(stat.cpu_jiffies, 300);
+        assert_eq!(stat.starttime, 5000);
+        assert_eq!(stat.rss_pages, 789);
+    }
+
+    #[test]
+    fn rejects_truncated_stat() {
+        assert!(ProcStat::parse(1, "1 (init) S 0 1 1 0 -1").is_none());
+        assert!(ProcStat::parse(1, "garbage").is_none());
+    }
+
+    #[test]
+    fn selects_pattern_matches_and_top_n() {
+        let config = ProcessCollectorConfig {
+            track_patterns: vec!["^postgres".into()],
+            track_top_n: 1,
+            ..Default::default()
+        };
+        let collector = ProcessCollector::new(&config).unwrap();
+        let procs = [
+            proc(1, "postgres", 10, None),
+            proc(2, "java", 5000, Some(1.0)),
+            proc(3, "ffmpeg", 100, Some(90.0)),
+            proc(4, "bash", 50, Some(0.5)),
+        ];
+
+        assert_eq!(collector.select(&procs), [0, 1, 2]);
+    }
+}
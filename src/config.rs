@@ -1,6 +1,5 @@
 use anyhow::{Context, Result};
-use serde::Deserialize;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
 
 /// Top-level configuration
 #[derive(Debug, Deserialize, Clone)]
@@ -18,6 +17,8 @@ pub struct Config {
     pub alerting: AlertingConfig,
     #[serde(default)]
     pub prometheus: PrometheusConfig,
+    #[serde(default)]
+    pub control: ControlConfig,
     #[cfg(feature = "nats")]
     #[serde(default)]
     pub nats: NatsConfig,
@@ -99,6 +100,10 @@ pub struct DiskCollectorConfig {
     pub exclude_fstypes: Vec<String>,
     #[serde(default)]
     pub exclude_mountpoints: Vec<String>,
+    #[serde(default = "default_exclude_devices")]
+    pub exclude_devices: Vec<String>,
+    #[serde(default)]
+    pub include_partitions: bool,
 }
 
 impl Default for DiskCollectorConfig {
@@ -109,6 +114,8 @@ impl Default for DiskCollectorConfig {
             io_interval_secs: 10,
             exclude_fstypes: default_exclude_fstypes(),
             exclude_mountpoints: vec![],
+            exclude_devices: default_exclude_devices(),
+            include_partitions: false,
         }
     }
 }
@@ -162,9 +169,13 @@ pub struct LogCollectorConfig {
     pub enabled: bool,
     #[serde(default = "default_log_sources")]
     pub sources: Vec<String>,
+    /// File tailed for the `syslog` source; `/var/log/syslog` or `/var/log/messages` when unset
     pub syslog_path: Option<String>,
     #[serde(default)]
     pub custom_patterns: Vec<CustomPattern>,
+    /// Further log files to tail, each with its own patterns
+    #[serde(default)]
+    pub files: Vec<LogFileConfig>,
 }
 
 impl Default for LogCollectorConfig {
@@ -174,10 +185,23 @@ impl Default for LogCollectorConfig {
             sources: default_log_sources(),
             syslog_path: None,
             custom_patterns: vec![],
+            files: vec![],
         }
     }
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct LogFileConfig {
+    /// File path or glob, e.g. `/var/log/app/*.log`; should not match rotated files
+    pub path: String,
+    /// Patterns matched against this file's lines only
+    #[serde(default)]
+    pub patterns: Vec<CustomPattern>,
+    /// Also match the built-in patterns and `custom_patterns`
+    #[serde(default = "default_true")]
+    pub default_patterns: bool,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CustomPattern {
     pub name: String,
@@ -277,24 +301,92 @@ pub struct AlertingConfig {
     pub rate_limit_per_minute: u32,
     #[serde(default = "default_rate_per_hour")]
     pub rate_limit_per_hour: u32,
+    #[serde(default = "default_rate_per_metric_per_hour")]
+    pub rate_limit_per_metric_per_hour: u32,
     #[serde(default = "default_dedup_window")]
     pub dedup_window_secs: u64,
+    #[serde(default = "default_repeat_interval")]
+    pub repeat_interval_secs: u64,
     #[serde(default = "default_group_window")]
     pub group_window_secs: u64,
-    #[serde(default = "default_true")]
-    pub recovery_enabled: bool,
-    #[serde(default)]
-    pub discord: Option<DiscordConfig>,
+    /// Labels that, with the hostname, form the grouping key; metric family when empty
     #[serde(default)]
-    pub slack: Option<SlackConfig>,
+    pub group_by: Vec<String>,
     #[serde(default)]
-    pub telegram: Option<TelegramConfig>,
+    pub escalation: Vec<EscalationConfig>,
     #[serde(default)]
-    pub email: Option<EmailConfig>,
+    pub silences: Vec<SilenceConfig>,
+    /// Message templates keyed by channel type or instance name, with `default` for all others
     #[serde(default)]
-    pub webhook: Option<WebhookConfig>,
+    pub templates: std::collections::HashMap<String, String>,
+    /// Runbook URLs keyed by metric name, metric family or `default`
     #[serde(default)]
-    pub syslog: Option<SyslogConfig>,
+    pub runbooks: std::collections::HashMap<String, String>,
+    #[serde(default = "default_true")]
+    pub recovery_enabled: bool,
+    /// How long a condition must stay clear before a RESOLVED is sent
+    #[serde(default = "default_recovery_clear")]
+    pub recovery_clear_secs: u64,
+    /// Delivery attempts per notification before it is dead-lettered
+    #[serde(default = "default_retry_max_attempts")]
+    pub retry_max_attempts: u32,
+    /// Backoff before the first retry; doubles on each further attempt
+    #[serde(default = "default_retry_backoff")]
+    pub retry_backoff_secs: u64,
+    #[serde(default = "default_retry_max_backoff")]
+    pub retry_max_backoff_secs: u64,
+    /// How often a channel with dead-lettered notifications is probed again
+    #[serde(default = "default_dead_letter_replay")]
+    pub dead_letter_replay_secs: u64,
+    /// Dead-lettered notifications kept per channel; the oldest are dropped beyond this
+    #[serde(default = "default_dead_letter_max_entries")]
+    pub dead_letter_max_entries: usize,
+    /// Dead-lettered notifications older than this are dropped instead of replayed
+    #[serde(default = "default_dead_letter_max_age")]
+    pub dead_letter_max_age_hours: u64,
+    /// Consecutive failed sends that open a channel's circuit breaker
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub breaker_failure_threshold: u32,
+    /// How long an open breaker rejects sends before letting a trial through
+    #[serde(default = "default_breaker_recovery")]
+    pub breaker_recovery_secs: u64,
+    /// Successful trial sends that close a half-open breaker
+    #[serde(default = "default_breaker_success_threshold")]
+    pub breaker_success_threshold: u32,
+    /// Record alert decisions and delivery outcomes in `{data_dir}/journal`
+    #[serde(default = "default_true")]
+    pub journal_enabled: bool,
+    /// Size at which the journal file is rotated
+    #[serde(default = "default_journal_max_file_mb")]
+    pub journal_max_file_mb: u64,
+    /// Journal files kept, the current one included; the oldest is deleted on rotation
+    #[serde(default = "default_journal_max_files")]
+    pub journal_max_files: u32,
+    /// Routing tree choosing the channels for each alert; all channels when absent
+    #[serde(default)]
+    pub route: Option<RouteConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub discord: Vec<DiscordConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub slack: Vec<SlackConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub telegram: Vec<TelegramConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub email: Vec<EmailConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub webhook: Vec<WebhookConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub syslog: Vec<SyslogConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pagerduty: Vec<PagerDutyConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub teams: Vec<TeamsConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub mattermost: Vec<MattermostConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub google_chat: Vec<GoogleChatConfig>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub ntfy: Vec<NtfyConfig>,
 }
 
 impl Default for AlertingConfig {
@@ -302,72 +394,216 @@ impl Default for AlertingConfig {
         Self {
             rate_limit_per_minute: 10,
             rate_limit_per_hour: 60,
+            rate_limit_per_metric_per_hour: 20,
             dedup_window_secs: 300,
+            repeat_interval_secs: 3600,
             group_window_secs: 30,
+            group_by: Vec::new(),
+            escalation: Vec::new(),
+            silences: Vec::new(),
+            templates: std::collections::HashMap::new(),
+            runbooks: std::collections::HashMap::new(),
             recovery_enabled: true,
-            discord: None,
-            slack: None,
-            telegram: None,
-            email: None,
-            webhook: None,
-            syslog: None,
+            recovery_clear_secs: 60,
+            retry_max_attempts: 4,
+            retry_backoff_secs: 2,
+            retry_max_backoff_secs: 60,
+            dead_letter_replay_secs: 300,
+            dead_letter_max_entries: 1000,
+            dead_letter_max_age_hours: 24,
+            breaker_failure_threshold: 5,
+            breaker_recovery_secs: 60,
+            breaker_success_threshold: 2,
+            journal_enabled: true,
+            journal_max_file_mb: 10,
+            journal_max_files: 5,
+            route: None,
+            discord: Vec::new(),
+            slack: Vec::new(),
+            telegram: Vec::new(),
+            email: Vec::new(),
+            webhook: Vec::new(),
+            syslog: Vec::new(),
+            pagerduty: Vec::new(),
+            teams: Vec::new(),
+            mattermost: Vec::new(),
+            google_chat: Vec::new(),
+            ntfy: Vec::new(),
         }
     }
 }
 
+/// Silence: alerts matching every matcher are not notified while it is active.
+/// Active between `starts_at` and `ends_at` (either may be open), or, with a
+/// `schedule`, for `duration_mins` after each time the cron expression matches.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SilenceConfig {
+    /// Assigned when added at runtime; defaults to `config-<index>` for configured silences
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub metric: Option<String>,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    /// Severities to silence; all when empty
+    #[serde(default)]
+    pub severity: Vec<String>,
+    #[serde(default)]
+    pub starts_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub ends_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Five-field cron expression (local time) opening a recurring window
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub duration_mins: Option<u64>,
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// Routing tree node. An alert descends into the first child route whose
+/// matchers all hold (and further siblings while `continue` is set); the
+/// receivers of the deepest matching routes get the alert. Routes without
+/// receivers inherit their parent's; the root defaults to every channel.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RouteConfig {
+    /// Severities to match; any when empty
+    #[serde(default)]
+    pub severity: Vec<String>,
+    /// Metric name, or a prefix ending in `*` (e.g. `disk.*`)
+    #[serde(default)]
+    pub metric: Option<String>,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    /// Analyzer that raised the alert (`threshold`, `zscore`, `trend`, `log`)
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Channel instance names
+    #[serde(default)]
+    pub receivers: Vec<String>,
+    #[serde(default, rename = "continue")]
+    pub continue_matching: bool,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+}
+
+/// Escalation policy, matched on metric and/or labels (all must match)
+#[derive(Debug, Deserialize, Clone)]
+pub struct EscalationConfig {
+    #[serde(default)]
+    pub metric: Option<String>,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    /// Escalate Warn to Critical after this many consecutive occurrences
+    #[serde(default)]
+    pub warn_to_critical_after: Option<u32>,
+    /// Escalate Critical to Emergency after this many minutes unresolved
+    #[serde(default)]
+    pub critical_to_emergency_after_mins: Option<u64>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DiscordConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_discord_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     pub webhook_url: String,
     pub username: Option<String>,
     #[serde(default)]
     pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SlackConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_slack_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     pub webhook_url: String,
     pub channel: Option<String>,
     #[serde(default)]
     pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TelegramConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_telegram_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     pub bot_token: String,
     pub chat_id: String,
     #[serde(default)]
     pub severity_filter: Vec<String>,
     #[serde(default = "default_parse_mode")]
     pub parse_mode: String,
+    #[serde(default = "default_telegram_api_url")]
+    pub api_url: String,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct EmailConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_email_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     pub smtp_host: String,
     #[serde(default = "default_smtp_port")]
     pub smtp_port: u16,
     #[serde(default = "default_true")]
     pub smtp_tls: bool,
+    /// "starttls", "tls" (implicit) or "none"; overrides `smtp_tls` when set
+    #[serde(default)]
+    pub tls_mode: Option<String>,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub password: String,
     pub from: String,
     pub to: Vec<String>,
     #[serde(default)]
     pub severity_filter: Vec<String>,
+    /// Alerts queued within this many seconds of the first share one email; 0 sends each on its own
+    #[serde(default = "default_email_batch_window")]
+    pub batch_window_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct WebhookConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_webhook_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     pub url: String,
     #[serde(default = "default_post")]
     pub method: String,
@@ -375,16 +611,170 @@ pub struct WebhookConfig {
     pub headers: std::collections::HashMap<String, String>,
     #[serde(default)]
     pub severity_filter: Vec<String>,
+    /// JSON body rendered per alert, replacing the built-in payload;
+    /// substituted values are JSON-escaped
+    #[serde(default)]
+    pub body_template: Option<String>,
+    /// HMAC-SHA256 key; when set, requests carry a timestamp and signature header
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+    #[serde(default = "default_signature_header")]
+    pub signature_header: String,
+    #[serde(default = "default_timestamp_header")]
+    pub timestamp_header: String,
+    #[serde(default)]
+    pub bearer_token: Option<String>,
+    #[serde(default)]
+    pub basic_auth: Option<BasicAuthConfig>,
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+    /// PEM bundle of CAs trusted in addition to the system roots
+    #[serde(default)]
+    pub tls_ca_file: Option<String>,
+    /// PEM client certificate; may also contain the key
+    #[serde(default)]
+    pub tls_cert_file: Option<String>,
+    #[serde(default)]
+    pub tls_key_file: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SyslogConfig {
     #[serde(default)]
     pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_syslog_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
     #[serde(default = "default_facility")]
     pub facility: String,
     #[serde(default)]
     pub severity_filter: Vec<String>,
+    #[serde(default = "default_syslog_socket")]
+    pub socket_path: String,
+    /// Remote collector ("host:port") used when the local socket is unavailable
+    #[serde(default)]
+    pub remote: Option<String>,
+    #[serde(default = "default_syslog_protocol")]
+    pub protocol: String,
+    #[serde(default = "default_syslog_app_name")]
+    pub app_name: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PagerDutyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_pagerduty_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
+    /// Integration key of an Events API v2 service integration
+    pub routing_key: String,
+    #[serde(default = "default_pagerduty_events_url")]
+    pub events_url: String,
+    #[serde(default)]
+    pub severity_filter: Vec<String>,
+    /// Link shown with each incident, e.g. a dashboard for the host
+    #[serde(default)]
+    pub client_url: Option<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct TeamsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_teams_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
+    /// Incoming webhook or Workflows ("When a Teams webhook request is received") URL
+    pub webhook_url: String,
+    #[serde(default)]
+    pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MattermostConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_mattermost_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
+    pub webhook_url: String,
+    /// Overrides the webhook's channel if the webhook allows it
+    pub channel: Option<String>,
+    pub username: Option<String>,
+    pub icon_url: Option<String>,
+    #[serde(default)]
+    pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GoogleChatConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_google_chat_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
+    /// Space webhook URL, including its `key` and `token` parameters
+    pub webhook_url: String,
+    #[serde(default)]
+    pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NtfyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Instance name used by routes and logs; must be unique across channels
+    #[serde(default = "default_ntfy_name")]
+    pub name: String,
+    /// Channel that takes this one's alerts while its circuit breaker is open
+    #[serde(default)]
+    pub fallback: Option<String>,
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+    pub topic: String,
+    /// Access token for protected topics
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub severity_filter: Vec<String>,
+    /// Seconds before a request is given up
+    #[serde(default = "default_http_timeout")]
+    pub timeout_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -401,6 +791,22 @@ impl Default for PrometheusConfig {
     }
 }
 
+/// Local control socket for runtime commands (silences)
+#[derive(Debug, Deserialize, Clone)]
+pub struct ControlConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Defaults to `control.sock` in `agent.data_dir`
+    #[serde(default)]
+    pub socket_path: Option<String>,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self { enabled: true, socket_path: None }
+    }
+}
+
 #[cfg(feature = "nats")]
 #[derive(Debug, Deserialize, Clone)]
 pub struct NatsConfig {
@@ -456,6 +862,36 @@ impl Config {
     }
 }
 
+/// Accept a single table (`[alerting.slack]`) or an array of tables
+/// (`[[alerting.slack]]`) for channels that may have several instances
+fn one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+
+    struct Visitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a table or an array of tables")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> std::result::Result<Vec<T>, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|t| vec![t])
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(self, seq: A) -> std::result::Result<Vec<T>, A::Error> {
+            Vec::<T>::deserialize(SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(Visitor(std::marker::PhantomData))
+}
+
 /// Expand ${ENV_VAR} references in config string
 fn expand_env_vars(input: &str) -> String {
     let re = regex::Regex::new(r"\$\{([^}]+)\}").unwrap();
@@ -485,6 +921,7 @@ fn default_log_sources() -> Vec<String> { vec!["dmesg".into(), "syslog".into()]
 fn default_exclude_fstypes() -> Vec<String> {
     vec!["tmpfs".into(), "devtmpfs".into(), "sysfs".into(), "proc".into()]
 }
+fn default_exclude_devices() -> Vec<String> { vec!["loop".into(), "ram".into()] }
 fn default_exclude_interfaces() -> Vec<String> { vec!["lo".into()] }
 fn default_80() -> f64 { 80.0 }
 fn default_90() -> f64 { 90.0 }
@@ -499,13 +936,48 @@ fn default_ring_buffer_size() -> u32 { 8640 }
 fn default_retention_days() -> u32 { 30 }
 fn default_rate_per_min() -> u32 { 10 }
 fn default_rate_per_hour() -> u32 { 60 }
+fn default_rate_per_metric_per_hour() -> u32 { 20 }
 fn default_dedup_window() -> u64 { 300 }
+fn default_repeat_interval() -> u64 { 3600 }
 fn default_group_window() -> u64 { 30 }
+fn default_recovery_clear() -> u64 { 60 }
+fn default_retry_max_attempts() -> u32 { 4 }
+fn default_retry_backoff() -> u64 { 2 }
+fn default_retry_max_backoff() -> u64 { 60 }
+fn default_dead_letter_replay() -> u64 { 300 }
+fn default_dead_letter_max_entries() -> usize { 1000 }
+fn default_dead_letter_max_age() -> u64 { 24 }
+fn default_breaker_failure_threshold() -> u32 { 5 }
+fn default_breaker_recovery() -> u64 { 60 }
+fn default_breaker_success_threshold() -> u32 { 2 }
+fn default_journal_max_file_mb() -> u64 { 10 }
+fn default_journal_max_files() -> u32 { 5 }
 fn default_parse_mode() -> String { "HTML".to_string() }
+fn default_telegram_api_url() -> String { "https://api.telegram.org".to_string() }
 fn default_smtp_port() -> u16 { 587 }
+fn default_email_batch_window() -> u64 { 10 }
 fn default_post() -> String { "POST".to_string() }
 fn default_facility() -> String { "daemon".to_string() }
+fn default_syslog_socket() -> String { "/dev/log".to_string() }
+fn default_syslog_protocol() -> String { "udp".to_string() }
+fn default_syslog_app_name() -> String { "sysops-agent".to_string() }
+fn default_pagerduty_events_url() -> String { "https://events.pagerduty.com/v2/enqueue".to_string() }
+fn default_signature_header() -> String { "X-SysOps-Signature".to_string() }
+fn default_timestamp_header() -> String { "X-SysOps-Timestamp".to_string() }
+fn default_http_timeout() -> u64 { 10 }
+fn default_ntfy_server() -> String { "https://ntfy.sh".to_string() }
 fn default_prom_bind() -> String { "127.0.0.1:9100".to_string() }
+fn default_discord_name() -> String { "discord".to_string() }
+fn default_slack_name() -> String { "slack".to_string() }
+fn default_telegram_name() -> String { "telegram".to_string() }
+fn default_email_name() -> String { "email".to_string() }
+fn default_webhook_name() -> String { "webhook".to_string() }
+fn default_syslog_name() -> String { "syslog".to_string() }
+fn default_pagerduty_name() -> String { "pagerduty".to_string() }
+fn default_teams_name() -> String { "teams".to_string() }
+fn default_mattermost_name() -> String { "mattermost".to_string() }
+fn default_google_chat_name() -> String { "google_chat".to_string() }
+fn default_ntfy_name() -> String { "ntfy".to_string() }
 #[cfg(feature = "nats")]
 fn default_nats_url() -> String { "nats://localhost:4222".to_string() }
 #[cfg(feature = "nats")]
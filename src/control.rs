@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, info};
+
+use crate::alerter::{ControlMessage, ControlRequest, ControlResponse};
+
+/// Serve runtime commands on a local Unix socket. Each line received is a
+/// JSON `ControlRequest`; each gets one JSON `ControlResponse` line back.
+/// The socket is only accessible to the agent's user.
+pub fn spawn(path: &str, tx: mpsc::Sender<ControlMessage>) -> Result<()> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    // A socket left behind by a previous run would make bind fail
+    if path.exists() {
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    info!(path = %path.display(), "Control socket listening");
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, tx).await {
+                            debug!(error = %e, "Control connection closed with error");
+                        }
+                    });
+                }
+                Err(e) => error!(error = %e, "Failed to accept control connection"),
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn handle_connection(stream: UnixStream, tx: mpsc::Sender<ControlMessage>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_request(&line, &tx).await;
+        let mut payload = serde_json::to_vec(&response)?;
+        payload.push(b'\n');
+        writer.write_all(&payload).await?;
+    }
+    Ok(())
+}
+
+/// Parse a JSON request and forward it to the alert manager
+pub async fn handle_request(payload: &str, tx: &mpsc::Sender<ControlMessage>) -> ControlResponse {
+    let request: ControlRequest = match serde_json::from_str(payload) {
+        Ok(r) => r,
+        Err(e) => return ControlResponse::error(format!("Invalid request: {}", e)),
+    };
+    let (reply, rx) = oneshot::channel();
+    if tx.send(ControlMessage { request, reply }).await.is_err() {
+        return ControlResponse::error("Alert manager is not running");
+    }
+    rx.await.unwrap_or_else(|_| ControlResponse::error("Alert manager dropped the request"))
+}
@@ -4,6 +4,13 @@ pub mod alerter;
 pub mod config;
 pub mod storage;
 pub mod log_analyzer;
+pub mod log_tail;
+pub mod self_metrics;
+pub mod control;
+#[cfg(feature = "prometheus")]
+pub mod prometheus_exporter;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
 #[cfg(feature = "nats")]
 pub mod nats_publisher;
 #[cfg(feature = "nats")]
@@ -41,9 +48,16 @@ pub mod types {
         MemSwapUsage,
         DiskUsage,
         DiskAvailable,
+        DiskTotal,
+        DiskFree,
         DiskInodeUsage,
         DiskReadRate,
         DiskWriteRate,
+        DiskReadOps,
+        DiskWriteOps,
+        DiskAwait,
+        DiskServiceTime,
+        DiskQueueDepth,
         DiskIoTime,
         NetRxRate,
         NetTxRate,
@@ -59,7 +73,7 @@ pub mod types {
     }
 
     /// A label key-value pair
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
     pub struct Label {
         pub key: String,
         pub value: String,
@@ -74,8 +88,17 @@ pub mod types {
         Emergency,
     }
 
+    /// Whether an alert reports a condition starting or clearing
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum AlertStatus {
+        #[default]
+        Firing,
+        Resolved,
+    }
+
     /// An alert to be dispatched
-    #[derive(Debug, Clone, Serialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Alert {
         pub timestamp: DateTime<Utc>,
         pub severity: Severity,
@@ -85,6 +108,20 @@ pub mod types {
         pub message: String,
         pub labels: SmallVec<[Label; 4]>,
         pub hostname: String,
+        /// Name of the analyzer that raised the alert
+        pub source: String,
+        pub status: AlertStatus,
+        /// When the condition was first detected (set by the alert manager)
+        pub started_at: Option<DateTime<Utc>>,
+        /// Severity raised by the analyzer, if an escalation policy raised it
+        pub escalated_from: Option<Severity>,
+    }
+
+    impl Alert {
+        /// How long the condition has been (or was) active
+        pub fn active_duration(&self) -> Option<chrono::Duration> {
+            self.started_at.map(|start| self.timestamp - start)
+        }
     }
 
     impl std::fmt::Display for Severity {
@@ -98,6 +135,58 @@ pub mod types {
         }
     }
 
+    impl MetricId {
+        /// Every metric, in declaration order
+        pub const ALL: [MetricId; 34] = [
+            MetricId::CpuUsage,
+            MetricId::CpuUsagePerCore,
+            MetricId::CpuIoWait,
+            MetricId::CpuSteal,
+            MetricId::CpuLoad1m,
+            MetricId::CpuLoad5m,
+            MetricId::CpuLoad15m,
+            MetricId::MemUsage,
+            MetricId::MemAvailable,
+            MetricId::MemSwapUsage,
+            MetricId::DiskUsage,
+            MetricId::DiskAvailable,
+            MetricId::DiskTotal,
+            MetricId::DiskFree,
+            MetricId::DiskInodeUsage,
+            MetricId::DiskReadRate,
+            MetricId::DiskWriteRate,
+            MetricId::DiskReadOps,
+            MetricId::DiskWriteOps,
+            MetricId::DiskAwait,
+            MetricId::DiskServiceTime,
+            MetricId::DiskQueueDepth,
+            MetricId::DiskIoTime,
+            MetricId::NetRxRate,
+            MetricId::NetTxRate,
+            MetricId::NetRxErrors,
+            MetricId::NetTxErrors,
+            MetricId::ProcCount,
+            MetricId::ProcRss,
+            MetricId::ProcCpu,
+            MetricId::ProcFdCount,
+            MetricId::FdSystemUsage,
+            MetricId::KernelEntropy,
+            MetricId::KernelUptime,
+        ];
+    }
+
+    impl std::str::FromStr for MetricId {
+        type Err = String;
+
+        /// Parse the dotted name produced by `Display`, e.g. `disk.usage_percent`
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            MetricId::ALL.iter()
+                .find(|m| m.to_string() == s)
+                .copied()
+                .ok_or_else(|| format!("unknown metric: {}", s))
+        }
+    }
+
     impl std::fmt::Display for MetricId {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             let s = match self {
@@ -113,9 +202,16 @@ pub mod types {
                 MetricId::MemSwapUsage => "mem.swap_usage_percent",
                 MetricId::DiskUsage => "disk.usage_percent",
                 MetricId::DiskAvailable => "disk.available_bytes",
+                MetricId::DiskTotal => "disk.total_bytes",
+                MetricId::DiskFree => "disk.free_bytes",
                 MetricId::DiskInodeUsage => "disk.inode_usage_percent",
                 MetricId::DiskReadRate => "disk.read_bytes_rate",
                 MetricId::DiskWriteRate => "disk.write_bytes_rate",
+                MetricId::DiskReadOps => "disk.read_ops_rate",
+                MetricId::DiskWriteOps => "disk.write_ops_rate",
+                MetricId::DiskAwait => "disk.await_ms",
+                MetricId::DiskServiceTime => "disk.service_time_ms",
+                MetricId::DiskQueueDepth => "disk.queue_depth",
                 MetricId::DiskIoTime => "disk.io_time_percent",
                 MetricId::NetRxRate => "net.rx_bytes_rate",
                 MetricId::NetTxRate => "net.tx_bytes_rate",
@@ -1,16 +1,47 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::Utc;
 use regex::Regex;
-use smallvec::smallvec;
+use smallvec::{smallvec, SmallVec};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-use crate::config::Config;
-use crate::types::{Alert, MetricId, Severity};
+use crate::config::{Config, CustomPattern};
+use crate::log_tail::LogTailer;
+use crate::types::{Alert, AlertStatus, Label, MetricId, Severity};
+
+/// `Alert::source` for log pattern matches. These are one-off events rather
+/// than ongoing conditions, so they never produce recovery notifications.
+pub const SOURCE: &str = "log";
+
+/// Syslog files tried, in order, when `syslog_path` is unset
+const SYSLOG_PATHS: &[&str] = &["/var/log/syslog", "/var/log/messages"];
 
 /// Analyzes kernel and system logs for critical events
 pub struct LogAnalyzer {
-    patterns: Vec<LogPattern>,
+    /// Built-in and `custom_patterns` patterns
+    patterns: Arc<Vec<LogPattern>>,
+    /// Read on a blocking thread, as tailing is file I/O
+    files: Arc<Mutex<LogFiles>>,
+    dmesg: bool,
     hostname: String,
-    // TODO: track file position for syslog tailing
+}
+
+/// The tailed files and how far each has been read
+struct LogFiles {
+    patterns: Arc<Vec<LogPattern>>,
+    /// Syslog, then `files`
+    sources: Vec<FileSource>,
+    tailer: LogTailer,
+    hostname: String,
+}
+
+/// A tailed file path or glob with the patterns for its lines
+struct FileSource {
+    /// `None` for syslog at whichever of `SYSLOG_PATHS` exists at the time
+    path: Option<String>,
+    patterns: Vec<LogPattern>,
+    default_patterns: bool,
 }
 
 struct LogPattern {
@@ -57,60 +88,168 @@ impl LogAnalyzer {
 
         // Add custom patterns from config
         for cp in &config.collector.log.custom_patterns {
-            let severity = match cp.severity.as_str() {
-                "info" => Severity::Info,
-                "warn" => Severity::Warn,
-                "critical" => Severity::Critical,
-                "emergency" => Severity::Emergency,
-                _ => Severity::Warn,
-            };
-            patterns.push(LogPattern {
-                name: cp.name.clone(),
-                regex: Regex::new(&cp.pattern)?,
-                severity,
-                metric: MetricId::KernelEntropy,
+            patterns.push(LogPattern::custom(cp)?);
+        }
+
+        let log = &config.collector.log;
+        let mut sources = Vec::new();
+        if log.sources.iter().any(|s| s == "syslog") {
+            if log.syslog_path.is_none() && find_syslog().is_none() {
+                tracing::info!("No syslog file found, set collector.log.syslog_path to tail one");
+            }
+            sources.push(FileSource { path: log.syslog_path.clone(), patterns: Vec::new(), default_patterns: true });
+        }
+        for fc in &log.files {
+            glob::Pattern::new(&fc.path).with_context(|| format!("Invalid log file path `{}`", fc.path))?;
+            sources.push(FileSource {
+                path: Some(fc.path.clone()),
+                patterns: fc.patterns.iter().map(LogPattern::custom).collect::<Result<_>>()?,
+                default_patterns: fc.default_patterns,
             });
         }
 
         let hostname = config.agent.hostname.clone();
+        let patterns = Arc::new(patterns);
+        let files = LogFiles {
+            patterns: patterns.clone(),
+            sources,
+            tailer: LogTailer::new(&config.agent.data_dir),
+            hostname: hostname.clone(),
+        };
 
-        Ok(Self { patterns, hostname })
+        Ok(Self {
+            patterns,
+            files: Arc::new(Mutex::new(files)),
+            dmesg: log.sources.iter().any(|s| s == "dmesg"),
+            hostname,
+        })
     }
 
     pub async fn check(&mut self) -> Result<Vec<Alert>> {
         let mut alerts = Vec::new();
 
         // Read dmesg (requires CAP_SYSLOG)
-        if let Ok(output) = tokio::process::Command::new("dmesg")
-            .arg("--time-format=iso")
-            .arg("--level=err,crit,alert,emerg")
-            .output()
-            .await
-        {
-            if output.status.success() {
-                let content = String::from_utf8_lossy(&output.stdout);
-                for line in content.lines() {
-                    for pattern in &self.patterns {
-                        if pattern.regex.is_match(line) {
-                            alerts.push(Alert {
-                                timestamp: Utc::now(),
-                                severity: pattern.severity,
-                                metric: pattern.metric,
-                                value: 1.0,
-                                threshold: None,
-                                message: format!("{}: {}", pattern.name, line.trim()),
-                                labels: smallvec![],
-                                hostname: self.hostname.clone(),
-                            });
+        if self.dmesg {
+            if let Ok(output) = tokio::process::Command::new("dmesg")
+                .arg("--time-format=iso")
+                .arg("--level=err,crit,alert,emerg")
+                .output()
+                .await
+            {
+                if output.status.success() {
+                    let content = String::from_utf8_lossy(&output.stdout);
+                    for line in content.lines() {
+                        for pattern in self.patterns.iter() {
+                            if pattern.regex.is_match(line) {
+                                alerts.push(Alert {
+                                    timestamp: Utc::now(),
+                                    severity: pattern.severity,
+                                    metric: pattern.metric,
+                                    value: 1.0,
+                                    threshold: None,
+                                    message: format!("{}: {}", pattern.name, line.trim()),
+                                    labels: smallvec![],
+                                    hostname: self.hostname.clone(),
+                                    source: SOURCE.into(),
+                                    status: AlertStatus::Firing,
+                                    started_at: None,
+                                    escalated_from: None,
+                                });
+                            }
                         }
                     }
                 }
             }
         }
 
-        // TODO: Implement syslog file tailing
+        let files = self.files.clone();
+        let tailed = tokio::task::spawn_blocking(move || files.lock().unwrap().check()).await
+            .context("Log file tailing failed")?;
+        alerts.extend(tailed);
+
         // TODO: Implement systemd journal reading
 
         Ok(alerts)
     }
 }
+
+impl LogFiles {
+    /// Tail the files; a path matched by several sources belongs to the first
+    fn check(&mut self) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let mut seen = HashSet::new();
+        for (i, source) in self.sources.iter().enumerate() {
+            let Some(pattern) = source.path.clone().or_else(find_syslog) else { continue };
+            let Ok(paths) = glob::glob(&pattern) else { continue };
+            for path in paths.filter_map(Result::ok).filter(|p| p.is_file()) {
+                if !seen.insert(path.clone()) {
+                    continue;
+                }
+                for line in self.tailer.read(&path, i) {
+                    source.scan(&self.patterns, &path, &line, &self.hostname, &mut alerts);
+                }
+            }
+        }
+        // Files gone since the last check are read to their end
+        for (path, i) in self.tailer.unseen(&seen) {
+            for line in self.tailer.read(&path, i) {
+                self.sources[i].scan(&self.patterns, &path, &line, &self.hostname, &mut alerts);
+            }
+        }
+        self.tailer.finish();
+        alerts
+    }
+}
+
+/// First of `SYSLOG_PATHS` that exists
+fn find_syslog() -> Option<String> {
+    SYSLOG_PATHS.iter().find(|p| Path::new(p).exists()).map(|p| p.to_string())
+}
+
+impl LogPattern {
+    fn custom(cp: &CustomPattern) -> Result<Self> {
+        let severity = match cp.severity.as_str() {
+            "info" => Severity::Info,
+            "warn" => Severity::Warn,
+            "critical" => Severity::Critical,
+            "emergency" => Severity::Emergency,
+            _ => Severity::Warn,
+        };
+        Ok(Self {
+            name: cp.name.clone(),
+            regex: Regex::new(&cp.pattern).with_context(|| format!("Invalid log pattern `{}`", cp.name))?,
+            severity,
+            metric: MetricId::KernelEntropy,
+        })
+    }
+}
+
+impl FileSource {
+    /// Alert for every pattern of the source matching a line of `path`
+    fn scan(&self, defaults: &[LogPattern], path: &Path, line: &str, hostname: &str, alerts: &mut Vec<Alert>) {
+        let defaults = if self.default_patterns { defaults } else { &[] };
+        for pattern in self.patterns.iter().chain(defaults) {
+            if !pattern.regex.is_match(line) {
+                continue;
+            }
+            let labels: SmallVec<[Label; 4]> = smallvec![
+                Label { key: "file".into(), value: path.display().to_string() },
+                Label { key: "pattern".into(), value: pattern.name.clone() },
+            ];
+            alerts.push(Alert {
+                timestamp: Utc::now(),
+                severity: pattern.severity,
+                metric: pattern.metric,
+                value: 1.0,
+                threshold: None,
+                message: format!("{}: {}", pattern.name, line.trim()),
+                labels,
+                hostname: hostname.to_string(),
+                source: SOURCE.into(),
+                status: AlertStatus::Firing,
+                started_at: None,
+                escalated_from: None,
+            });
+        }
+    }
+}
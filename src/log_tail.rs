@@ -0,0 +1,448 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// File in `data_dir` holding how far each log file has been read
+const OFFSETS_FILE: &str = "log_offsets.json";
+/// Most read from one file per check; the rest is picked up on the next
+const MAX_READ_PER_CHECK: u64 = 4 * 1024 * 1024;
+/// A line this long without a newline is taken as it is
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// Identity of a file, which survives renames but not recreation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct FileId {
+    dev: u64,
+    inode: u64,
+}
+
+impl FileId {
+    fn of(meta: &std::fs::Metadata) -> Self {
+        Self { dev: meta.dev(), inode: meta.ino() }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedOffset {
+    #[serde(flatten)]
+    id: FileId,
+    offset: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SavedOffsets {
+    #[serde(default)]
+    files: BTreeMap<PathBuf, SavedOffset>,
+}
+
+/// A log file being followed
+struct Tracked {
+    file: File,
+    id: FileId,
+    /// End of the last complete line read
+    offset: u64,
+    /// Source the path was matched by
+    source: usize,
+}
+
+/// Follows log files by inode and offset. A file renamed away (rename
+/// rotation) is read to its end before the new file at the path is read;
+/// a file that shrinks (copytruncate) is read again from the start.
+/// Offsets are saved in `data_dir`, so lines read before a restart are not
+/// read again.
+pub struct LogTailer {
+    files: HashMap<PathBuf, Tracked>,
+    /// Files rotated away from a path and not read to their end yet, oldest
+    /// first. They are read one chunk per check, so a large backlog does not
+    /// stall the check; the file now at the path waits until they are done.
+    rotated: HashMap<PathBuf, VecDeque<Tracked>>,
+    /// Offsets from the last run not yet matched to a file
+    saved: HashMap<PathBuf, SavedOffset>,
+    path: PathBuf,
+    /// Whether the first scan is done. Files found then without a saved
+    /// offset are read from their end; files appearing later from the start.
+    started: bool,
+    dirty: bool,
+    /// Paths already reported as unreadable
+    unreadable: HashSet<PathBuf>,
+}
+
+impl LogTailer {
+    pub fn new(data_dir: &str) -> Self {
+        let path = PathBuf::from(data_dir).join(OFFSETS_FILE);
+        let saved: SavedOffsets = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!(error = %e, path = %path.display(), "Ignoring unreadable log offsets");
+                SavedOffsets::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SavedOffsets::default(),
+            Err(e) => {
+                warn!(error = %e, path = %path.display(), "Failed to read log offsets");
+                SavedOffsets::default()
+            }
+        };
+
+        Self {
+            files: HashMap::new(),
+            rotated: HashMap::new(),
+            saved: saved.files.into_iter().collect(),
+            path,
+            started: false,
+            dirty: false,
+            unreadable: HashSet::new(),
+        }
+    }
+
+    /// Complete lines added to the file at `path` since the last call,
+    /// including the rest of a file that was rotated away from it
+    pub fn read(&mut self, path: &Path, source: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        let meta = std::fs::metadata(path);
+
+        if let Some(tracked) = self.files.get_mut(path) {
+            match meta {
+                Ok(ref m) if FileId::of(m) == tracked.id => {
+                    if m.len() < tracked.offset {
+                        info!(path = %path.display(), "Log file truncated, reading from the start");
+                        tracked.offset = 0;
+                        self.dirty = true;
+                    }
+                }
+                _ => {
+                    // Rotated or deleted: lines written before that are still in the old file
+                    if meta.is_ok() {
+                        info!(path = %path.display(), "Log file rotated, following the new file");
+                    }
+                    if let Some(old) = self.files.remove(path) {
+                        self.rotated.entry(path.to_path_buf()).or_default().push_back(old);
+                    }
+                    self.dirty = true;
+                }
+            }
+        }
+
+        if meta.is_ok() && !self.files.contains_key(path) {
+            match self.open(path, source) {
+                Ok(tracked) => {
+                    self.unreadable.remove(path);
+                    self.files.insert(path.to_path_buf(), tracked);
+                    self.dirty = true;
+                }
+                Err(e) => {
+                    if self.unreadable.insert(path.to_path_buf()) {
+                        warn!(path = %path.display(), error = %format!("{:#}", e), "Cannot read log file");
+                    }
+                }
+            }
+        }
+
+        if self.read_rotated(path, &mut lines) {
+            return lines;
+        }
+        if let Some(tracked) = self.files.get_mut(path) {
+            self.dirty |= read_lines(tracked, &mut lines, false).moved;
+        }
+        lines
+    }
+
+    /// Read the next chunk of the oldest file rotated away from `path`.
+    /// Returns whether rotated files are still left to read.
+    fn read_rotated(&mut self, path: &Path, lines: &mut Vec<String>) -> bool {
+        let Some(queue) = self.rotated.get_mut(path) else { return false };
+        if let Some(old) = queue.front_mut() {
+            let progress = read_lines(old, lines, true);
+            self.dirty |= progress.moved;
+            if progress.at_end {
+                queue.pop_front();
+                self.dirty = true;
+            }
+        }
+        if queue.is_empty() {
+            self.rotated.remove(path);
+            return false;
+        }
+        true
+    }
+
+    fn open(&mut self, path: &Path, source: usize) -> Result<Tracked> {
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let meta = file.metadata()?;
+        let id = FileId::of(&meta);
+
+        let offset = match self.saved.remove(path) {
+            Some(saved) if saved.id == id && saved.offset <= meta.len() => saved.offset,
+            // Truncated while the agent was stopped
+            Some(saved) if saved.id == id => 0,
+            Some(saved) => {
+                // Rotated while the agent was stopped: finish the old file if it is still around
+                if let Some(old) = path.parent().and_then(|dir| find_by_id(dir, saved.id)) {
+                    info!(path = %path.display(), rotated = %old.display(), "Reading rest of log file rotated while stopped");
+                    if let Ok(file) = File::open(&old) {
+                        let rotated = Tracked { file, id: saved.id, offset: saved.offset, source };
+                        self.rotated.entry(path.to_path_buf()).or_default().push_back(rotated);
+                    }
+                }
+                0
+            }
+            // Only lines written from now on; earlier ones were never ours to report
+            None if !self.started => meta.len(),
+            None => 0,
+        };
+
+        info!(path = %path.display(), offset, "Following log file");
+        Ok(Tracked { file, id, offset, source })
+    }
+
+    /// Followed files not among `seen`, with their source, so they are
+    /// read to the end once their path is gone
+    pub fn unseen(&self, seen: &HashSet<PathBuf>) -> Vec<(PathBuf, usize)> {
+        let rotated = self.rotated.iter().filter_map(|(path, queue)| Some((path, queue.front()?)));
+        let unseen: HashMap<&PathBuf, usize> = self.files.iter()
+            .chain(rotated)
+            .filter(|(path, _)| !seen.contains(*path))
+            .map(|(path, t)| (path, t.source))
+            .collect();
+        unseen.into_iter().map(|(path, source)| (path.clone(), source)).collect()
+    }
+
+    /// End of a check: save the offsets if any moved
+    pub fn finish(&mut self) {
+        if !self.started {
+            self.started = true;
+            // Files that no longer exist
+            if !self.saved.is_empty() {
+                self.saved.clear();
+                self.dirty = true;
+            }
+        }
+        if self.dirty {
+            self.save();
+            self.dirty = false;
+        }
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.try_save() {
+            tracing::error!(error = %e, path = %self.path.display(), "Failed to persist log offsets");
+        }
+    }
+
+    fn try_save(&self) -> Result<()> {
+        // While rotated files are left, the oldest is saved: after a restart
+        // it is found again by its identity and read on from there
+        let rotated = self.rotated.iter().filter_map(|(path, queue)| Some((path, queue.front()?)));
+        let offsets = SavedOffsets {
+            files: self.files.iter()
+                .filter(|(path, _)| !self.rotated.contains_key(*path))
+                .chain(rotated)
+                .map(|(path, t)| (path.clone(), SavedOffset { id: t.id, offset: t.offset }))
+                .collect(),
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&offsets)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// How far a `read_lines` call got
+struct Progress {
+    /// Whether the offset moved
+    moved: bool,
+    /// Whether the read reached the end of the file (or failed)
+    at_end: bool,
+}
+
+/// Read complete lines from the tracked offset, at most `MAX_READ_PER_CHECK`
+/// bytes; with `to_end`, at the end of the file a last line without a
+/// newline too, as nothing more will be written to the file.
+fn read_lines(tracked: &mut Tracked, lines: &mut Vec<String>, to_end: bool) -> Progress {
+    let mut buf = Vec::new();
+    let read = tracked.file.seek(SeekFrom::Start(tracked.offset))
+        .and_then(|_| (&tracked.file).take(MAX_READ_PER_CHECK).read_to_end(&mut buf));
+    if let Err(e) = read {
+        warn!(error = %e, "Failed to read log file");
+        return Progress { moved: false, at_end: true };
+    }
+    let at_end = (buf.len() as u64) < MAX_READ_PER_CHECK;
+
+    let consumed = match buf.iter().rposition(|&b| b == b'\n') {
+        _ if to_end && at_end => buf.len(),
+        Some(last) => last + 1,
+        None if buf.len() >= MAX_LINE_LEN => buf.len(),
+        None => 0,
+    };
+    if consumed == 0 {
+        return Progress { moved: false, at_end };
+    }
+    lines.extend(
+        String::from_utf8_lossy(&buf[..consumed])
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(str::to_owned),
+    );
+    tracked.offset += consumed as u64;
+    Progress { moved: true, at_end }
+}
+
+/// File in `dir` with the given identity, e.g. `syslog.1` after rotation
+fn find_by_id(dir: &Path, id: FileId) -> Option<PathBuf> {
+    std::fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.metadata().is_ok_and(|m| m.is_file() && FileId::of(&m) == id))
+        .map(|entry| entry.path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sysops-agent-tail-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn append(path: &Path, text: &str) {
+        std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap()
+            .write_all(text.as_bytes()).unwrap();
+    }
+
+    /// A tailer past its first scan, following `log` from its current end
+    fn following(dir: &Path, log: &Path) -> LogTailer {
+        let mut tailer = LogTailer::new(dir.to_str().unwrap());
+        assert!(tailer.read(log, 0).is_empty());
+        tailer.finish();
+        tailer
+    }
+
+    #[test]
+    fn reads_complete_lines_added_after_the_first_scan() {
+        let dir = temp_dir("append");
+        let log = dir.join("app.log");
+        append(&log, "old line\n");
+        let mut tailer = following(&dir, &log);
+
+        append(&log, "first\nsecond\npart");
+        assert_eq!(tailer.read(&log, 0), ["first", "second"]);
+        append(&log, "ial\n");
+        assert_eq!(tailer.read(&log, 0), ["partial"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rereads_a_truncated_file_from_the_start() {
+        let dir = temp_dir("truncate");
+        let log = dir.join("app.log");
+        append(&log, "a fairly long line before copytruncate\n");
+        let mut tailer = following(&dir, &log);
+
+        std::fs::write(&log, "after\n").unwrap();
+
+        assert_eq!(tailer.read(&log, 0), ["after"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn finishes_a_rotated_file_before_the_new_one() {
+        let dir = temp_dir("rotate");
+        let log = dir.join("app.log");
+        append(&log, "seen\n");
+        let mut tailer = following(&dir, &log);
+
+        append(&log, "last old\nno newline");
+        std::fs::rename(&log, dir.join("app.log.1")).unwrap();
+        append(&log, "new\n");
+
+        assert_eq!(tailer.read(&log, 0), ["last old", "no newline", "new"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn drains_a_large_rotated_file_over_several_checks() {
+        let dir = temp_dir("drain");
+        let log = dir.join("app.log");
+        append(&log, "seen\n");
+        let mut tailer = following(&dir, &log);
+
+        let line = format!("{}\n", "x".repeat(1023));
+        let count = (MAX_READ_PER_CHECK as usize / line.len()) * 5 / 2;
+        append(&log, &line.repeat(count));
+        std::fs::rename(&log, dir.join("app.log.1")).unwrap();
+        append(&log, "new\n");
+
+        let mut checks = Vec::new();
+        loop {
+            let lines = tailer.read(&log, 0);
+            tailer.finish();
+            if lines.is_empty() {
+                break;
+            }
+            checks.push(lines);
+        }
+
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().flatten().take(count).all(|l| l.len() == 1023));
+        assert_eq!(checks.iter().map(Vec::len).sum::<usize>(), count + 1);
+        assert_eq!(checks[2].last().unwrap(), "new");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keeps_draining_a_rotated_file_after_a_restart() {
+        let dir = temp_dir("drain-restart");
+        let log = dir.join("app.log");
+        append(&log, "seen\n");
+        let mut tailer = following(&dir, &log);
+
+        let line = format!("{}\n", "y".repeat(1023));
+        let count = (MAX_READ_PER_CHECK as usize / line.len()) * 3 / 2;
+        append(&log, &line.repeat(count));
+        std::fs::rename(&log, dir.join("app.log.1")).unwrap();
+        append(&log, "new\n");
+        let first = tailer.read(&log, 0).len();
+        tailer.finish();
+        drop(tailer);
+
+        let mut tailer = LogTailer::new(dir.to_str().unwrap());
+        let rest = tailer.read(&log, 0);
+
+        assert_eq!(first + rest.len(), count + 1);
+        assert_eq!(rest.last().unwrap(), "new");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn resumes_a_rotated_file_after_a_restart() {
+        let dir = temp_dir("restart");
+        let log = dir.join("app.log");
+        append(&log, "seen\n");
+        let mut tailer = following(&dir, &log);
+
+        append(&log, "before restart\n");
+        assert_eq!(tailer.read(&log, 0), ["before restart"]);
+        tailer.finish();
+        drop(tailer);
+
+        // Rotated while the agent was stopped
+        append(&log, "while stopped\n");
+        std::fs::rename(&log, dir.join("app.log.1")).unwrap();
+        append(&log, "new\n");
+
+        let mut tailer = LogTailer::new(dir.to_str().unwrap());
+        assert_eq!(tailer.read(&log, 0), ["while stopped", "new"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
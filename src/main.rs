@@ -1,18 +1,21 @@
 use anyhow::Result;
-use clap::Parser;
-use tracing::{info, error};
-#[cfg(feature = "nats")]
+use clap::{Parser, Subcommand};
 use std::sync::Arc;
+use tracing::{info, error};
 
-use sysops_agent::{collector, analyzer, alerter, config, storage, log_analyzer};
+use sysops_agent::{collector, analyzer, alerter, config, control, storage, log_analyzer, self_metrics};
 #[cfg(feature = "nats")]
 use sysops_agent::{nats_publisher, nats_handlers, inventory};
+#[cfg(feature = "sqlite")]
+use sysops_agent::sqlite_store;
+#[cfg(feature = "prometheus")]
+use sysops_agent::prometheus_exporter;
 
 #[derive(Parser, Debug)]
 #[command(name = "sysops-agent", about = "Lightweight system monitoring agent")]
 struct Cli {
     /// Path to configuration file
-    #[arg(short, long, default_value = "/etc/sysops-agent/config.toml")]
+    #[arg(short, long, global = true, default_value = "/etc/sysops-agent/config.toml")]
     config: String,
 
     /// Validate config and exit
@@ -22,6 +25,50 @@ struct Cli {
     /// Print version and exit
     #[arg(short, long)]
     version: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the most recent alert journal entries
+    Journal(JournalArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct JournalArgs {
+    /// Entries at or after this time: RFC 3339 or relative, e.g. 12h, 7d
+    #[arg(long)]
+    since: Option<String>,
+
+    /// Entries at or before this time: RFC 3339 or relative
+    #[arg(long)]
+    until: Option<String>,
+
+    /// Only this severity (repeatable)
+    #[arg(long)]
+    severity: Vec<String>,
+
+    /// Only this metric or metric family, e.g. disk.usage_percent or disk
+    #[arg(long)]
+    metric: Option<String>,
+
+    /// Only this outcome, e.g. silenced, rate_limited, dead_lettered (repeatable)
+    #[arg(long)]
+    outcome: Vec<String>,
+
+    /// Only entries for this channel instance
+    #[arg(long)]
+    channel: Option<String>,
+
+    /// Most recent entries shown
+    #[arg(long, default_value_t = 100)]
+    limit: usize,
+
+    /// Print entries as JSON lines
+    #[arg(long)]
+    json: bool,
 }
 
 #[tokio::main(worker_threads = 2)]
@@ -37,10 +84,15 @@ async fn main() -> Result<()> {
     let config = config::Config::load(&cli.config)?;
 
     if cli.check {
+        alerter::validate(&config.alerting)?;
         println!("Configuration is valid.");
         return Ok(());
     }
 
+    if let Some(Command::Journal(args)) = cli.command {
+        return print_journal(&config, args);
+    }
+
     // Initialize logging
     init_logging(&config)?;
 
@@ -59,6 +111,50 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Read the journal files directly, so this works whether or not the agent is running
+fn print_journal(config: &config::Config, args: JournalArgs) -> Result<()> {
+    let query = alerter::journal::JournalQuery {
+        since: args.since,
+        until: args.until,
+        severity: args.severity,
+        metric: args.metric,
+        outcome: args.outcome,
+        channel: args.channel,
+        limit: Some(args.limit),
+    };
+    // Printed oldest first, like a log
+    for entry in alerter::journal::query(&config.agent.data_dir, &query)?.iter().rev() {
+        if args.json {
+            println!("{}", serde_json::to_string(entry)?);
+            continue;
+        }
+        let labels: Vec<String> = entry.labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let series = if labels.is_empty() {
+            entry.metric.clone()
+        } else {
+            format!("{}{{{}}}", entry.metric, labels.join(","))
+        };
+        let status = match entry.status {
+            sysops_agent::types::AlertStatus::Firing => entry.severity.to_string(),
+            sysops_agent::types::AlertStatus::Resolved => "RESOLVED".to_string(),
+        };
+        let mut line = format!(
+            "{}  {:<13} {:<12} {:<9} {}  {}",
+            entry.time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            entry.outcome.as_str(),
+            entry.channel.as_deref().unwrap_or("-"),
+            status,
+            series,
+            entry.message,
+        );
+        if let Some(ref detail) = entry.detail {
+            line.push_str(&format!("  ({})", detail));
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
 fn init_logging(config: &config::Config) -> Result<()> {
     let filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| {
@@ -78,6 +174,41 @@ async fn run(config: config::Config) -> Result<()> {
     // Initialize storage
     let storage = storage::Storage::new(&config.storage)?;
 
+    // Open SQLite history and warm up the ring buffers (if enabled)
+    #[cfg(feature = "sqlite")]
+    let sqlite_writer = if config.storage.sqlite_enabled {
+        let store = sqlite_store::SqliteStore::open(&config.storage, &config.agent.data_dir)?;
+        if let Err(e) = store.warm_up(&storage, config.storage.ring_buffer_size as usize) {
+            error!(error = %e, "Failed to warm up from SQLite, starting with empty buffers");
+        }
+        Some(store.spawn_writer()?)
+    } else {
+        None
+    };
+    #[cfg(feature = "sqlite")]
+    let sqlite_for_shutdown = sqlite_writer.clone();
+    #[cfg(not(feature = "sqlite"))]
+    if config.storage.sqlite_enabled {
+        tracing::warn!("storage.sqlite_enabled is set but the agent was built without the `sqlite` feature");
+    }
+
+    // Agent self-metrics, shared by the pipeline tasks and exporters
+    let self_metrics = Arc::new(self_metrics::SelfMetrics::new());
+
+    // Start Prometheus endpoint (if enabled)
+    #[cfg(feature = "prometheus")]
+    if config.prometheus.enabled {
+        prometheus_exporter::PrometheusExporter::new(
+            storage.clone(),
+            self_metrics.clone(),
+            config.agent.hostname.clone(),
+        ).spawn(&config.prometheus.bind).await?;
+    }
+    #[cfg(not(feature = "prometheus"))]
+    if config.prometheus.enabled {
+        tracing::warn!("prometheus.enabled is set but the agent was built without the `prometheus` feature");
+    }
+
     // Initialize collectors
     let collectors = collector::create_collectors(&config)?;
 
@@ -85,7 +216,19 @@ async fn run(config: config::Config) -> Result<()> {
     let analyzers = analyzer::create_analyzers(&config)?;
 
     // Initialize alerter
-    let mut alerter = alerter::AlertManager::new(&config.alerting)?;
+    let mut alerter = alerter::AlertManager::new(&config.alerting, &config.agent.data_dir)?;
+    self_metrics.watch_channels(alerter.outboxes().to_vec());
+
+    // Runtime commands for the alerter (silences) from the control socket and NATS
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::channel::<alerter::ControlMessage>(16);
+    if config.control.enabled {
+        let path = config.control.socket_path.clone().unwrap_or_else(|| {
+            format!("{}/control.sock", config.agent.data_dir.trim_end_matches('/'))
+        });
+        if let Err(e) = control::spawn(&path, control_tx.clone()) {
+            error!(error = %e, "Failed to start control socket, continuing without it");
+        }
+    }
 
     // Initialize log analyzer
     let log_analyzer = log_analyzer::LogAnalyzer::new(&config)?;
@@ -114,9 +257,19 @@ async fn run(config: config::Config) -> Result<()> {
     let (metric_tx, metric_rx) = tokio::sync::mpsc::channel(10_000);
     let (alert_tx, alert_rx) = tokio::sync::mpsc::channel(1_000);
 
+    // A series is stale once its collector has missed a few runs, and never
+    // sooner than 15 minutes
+    let stale_after_secs = collectors.iter()
+        .map(|c| c.interval_secs())
+        .max()
+        .unwrap_or(0)
+        .saturating_mul(3)
+        .max(15 * 60);
+
     // Spawn collector tasks
     for mut c in collectors {
         let tx = metric_tx.clone();
+        let sm = self_metrics.clone();
         tokio::spawn(async move {
             loop {
                 let interval = c.interval_secs();
@@ -129,6 +282,7 @@ async fn run(config: config::Config) -> Result<()> {
                         }
                     }
                     Err(e) => {
+                        sm.record_collector_error(c.name());
                         tracing::warn!(collector = c.name(), error = %e, "Collection failed");
                     }
                 }
@@ -138,8 +292,9 @@ async fn run(config: config::Config) -> Result<()> {
     }
     drop(metric_tx); // Drop our handle
 
-    // Spawn storage ingestion task (+ NATS metric buffering)
+    // Spawn storage ingestion task (+ NATS metric buffering, SQLite persistence)
     let storage_handle = storage.clone();
+    let sm_ingest = self_metrics.clone();
     let alert_tx_clone = alert_tx.clone();
     #[cfg(feature = "nats")]
     let nats_for_metrics = nats_pub.clone();
@@ -150,10 +305,29 @@ async fn run(config: config::Config) -> Result<()> {
             if let Some(ref np) = nats_for_metrics {
                 np.buffer_metric(&sample).await;
             }
+            #[cfg(feature = "sqlite")]
+            if let Some(ref w) = sqlite_writer {
+                w.write(&sample);
+            }
+            sm_ingest.record_sample();
             storage_handle.insert(sample);
         }
     });
 
+    // Spawn stale series pruning task (exited processes, unmounted filesystems)
+    let storage_for_prune = storage.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let cutoff = chrono::Utc::now() - chrono::Duration::seconds(stale_after_secs as i64);
+            let removed = storage_for_prune.prune_stale(cutoff);
+            if removed > 0 {
+                tracing::debug!(removed, "Pruned stale metric series");
+            }
+        }
+    });
+
     // Spawn analyzer task
     let storage_for_analyzer = storage.clone();
     tokio::spawn(async move {
@@ -195,18 +369,37 @@ async fn run(config: config::Config) -> Result<()> {
 
     // Spawn alerter task (+ NATS alert publishing)
     let mut alert_rx = alert_rx;
+    let sm_alerts = self_metrics.clone();
     #[cfg(feature = "nats")]
     let nats_for_alerts = nats_pub.clone();
-    tokio::spawn(async move {
-        while let Some(alert) = alert_rx.recv().await {
-            #[cfg(feature = "nats")]
-            if let Some(ref np) = nats_for_alerts {
-                np.publish_alert(&alert).await;
-            }
-            if let Err(e) = alerter.dispatch(alert).await {
-                tracing::error!(error = %e, "Alert dispatch failed");
+    let (alerter_stop_tx, mut alerter_stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let alerter_handle = tokio::spawn(async move {
+        let mut tick = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = &mut alerter_stop_rx => break,
+                maybe_alert = alert_rx.recv() => {
+                    let Some(alert) = maybe_alert else { break };
+                    sm_alerts.record_alert(&alert);
+                    #[cfg(feature = "nats")]
+                    if let Some(ref np) = nats_for_alerts {
+                        np.publish_alert(&alert, alerter.silenced_by(&alert)).await;
+                    }
+                    if let Err(e) = alerter.dispatch(alert) {
+                        tracing::error!(error = %e, "Alert dispatch failed");
+                    }
+                }
+                Some(msg) = control_rx.recv() => {
+                    alerter.handle_control(msg);
+                }
+                _ = tick.tick() => {
+                    if let Err(e) = alerter.tick() {
+                        tracing::error!(error = %e, "Alert manager tick failed");
+                    }
+                }
             }
         }
+        alerter.shutdown().await;
     });
 
     // Start NATS request-reply handlers (snapshot, exec)
@@ -222,6 +415,7 @@ async fn run(config: config::Config) -> Result<()> {
                         config.agent.hostname.clone(),
                         config.nats.subject_prefix.clone(),
                         storage.clone(),
+                        control_tx.clone(),
                     );
                     info!("NATS request-reply handlers started");
                 }
@@ -238,9 +432,10 @@ async fn run(config: config::Config) -> Result<()> {
         // Small delay to let server subscribe first
         let np_init = np.clone();
         let proc_root_init = config.agent.proc_root.clone();
+        let sm_init = self_metrics.clone();
         tokio::spawn(async move {
             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-            np_init.publish_heartbeat().await;
+            np_init.publish_heartbeat(sm_init.channel_health()).await;
             let (hw, sw) = inventory::collect_inventory(&proc_root_init);
             np_init.publish_inventory(&hw, &sw).await;
         });
@@ -258,12 +453,13 @@ async fn run(config: config::Config) -> Result<()> {
 
         // Heartbeat loop
         let np_hb = np.clone();
+        let sm_hb = self_metrics.clone();
         let hb_interval = config.nats.heartbeat_interval_secs;
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(hb_interval));
             loop {
                 interval.tick().await;
-                np_hb.publish_heartbeat().await;
+                np_hb.publish_heartbeat(sm_hb.channel_health()).await;
             }
         });
 
@@ -285,5 +481,13 @@ async fn run(config: config::Config) -> Result<()> {
     tokio::signal::ctrl_c().await?;
     info!("Received shutdown signal, exiting");
 
+    let _ = alerter_stop_tx.send(());
+    let _ = alerter_handle.await;
+
+    #[cfg(feature = "sqlite")]
+    if let Some(w) = sqlite_for_shutdown {
+        let _ = tokio::task::spawn_blocking(move || w.shutdown()).await;
+    }
+
     Ok(())
 }
@@ -6,8 +6,10 @@ use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::process::Command;
+use tokio::sync::mpsc;
 use tracing::{info, warn, error};
 
+use crate::alerter::ControlMessage;
 use crate::storage::Storage;
 
 #[derive(Deserialize)]
@@ -88,12 +90,13 @@ struct LoadSnapshot {
     load_15m: f64,
 }
 
-/// Start NATS request-reply handlers for snapshot and exec
+/// Start NATS request-reply handlers for snapshot, exec and silences
 pub fn start_handlers(
     client: Client,
     hostname: String,
     prefix: String,
     storage: Storage,
+    control: mpsc::Sender<ControlMessage>,
 ) {
     // Snapshot handler
     {
@@ -152,6 +155,30 @@ pub fn start_handlers(
             }
         });
     }
+
+    // Silence handler (same requests as the local control socket)
+    {
+        let client = client.clone();
+        let subject = format!("{}.{}.silences", prefix, hostname);
+        tokio::spawn(async move {
+            let mut sub = match client.subscribe(subject.clone()).await {
+                Ok(s) => s,
+                Err(e) => { error!(error=%e, "Failed to subscribe to silences"); return; }
+            };
+            info!(subject=%subject, "Listening for silence requests");
+
+            while let Some(msg) = sub.next().await {
+                let request = String::from_utf8_lossy(&msg.payload);
+                let response = crate::control::handle_request(&request, &control).await;
+                let payload = serde_json::to_vec(&response).unwrap_or_default();
+                if let Some(reply) = msg.reply {
+                    if let Err(e) = client.publish(reply, payload.into()).await {
+                        error!(error=%e, "Failed to reply to silence request");
+                    }
+                }
+            }
+        });
+    }
 }
 
 /// Validate that a command is safe to execute.
@@ -8,6 +8,7 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{info, warn, error, debug};
 
+use crate::alerter::delivery::ChannelHealth;
 use crate::config::NatsConfig;
 use crate::types::{MetricSample, Alert};
 
@@ -35,6 +36,9 @@ struct HeartbeatPayload {
     ip_address: Option<String>,
     os: Option<String>,
     arch: Option<String>,
+    /// Alert channel delivery health
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    channels: Vec<ChannelHealth>,
 }
 
 /// Payload matching server's Inventory
@@ -55,6 +59,9 @@ struct AlertPayload {
     threshold: Option<f64>,
     message: String,
     labels: serde_json::Value,
+    silenced: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    silence_id: Option<String>,
 }
 
 pub struct NatsPublisher {
@@ -157,8 +164,8 @@ impl NatsPublisher {
         }
     }
 
-    /// Publish an alert immediately
-    pub async fn publish_alert(&self, alert: &Alert) {
+    /// Publish an alert immediately, flagged with the silence suppressing its notification
+    pub async fn publish_alert(&self, alert: &Alert, silence_id: Option<&str>) {
         let labels = alert.labels.iter().map(|l| {
             (l.key.clone(), serde_json::Value::String(l.value.clone()))
         }).collect::<serde_json::Map<String, serde_json::Value>>();
@@ -170,6 +177,8 @@ impl NatsPublisher {
             threshold: alert.threshold,
             message: alert.message.clone(),
             labels: serde_json::Value::Object(labels),
+            silenced: silence_id.is_some(),
+            silence_id: silence_id.map(String::from),
         };
 
         match serde_json::to_vec(&payload) {
@@ -209,8 +218,8 @@ impl NatsPublisher {
         }
     }
 
-    /// Publish heartbeat
-    pub async fn publish_heartbeat(&self) {
+    /// Publish heartbeat with the current alert channel health
+    pub async fn publish_heartbeat(&self, channels: Vec<ChannelHealth>) {
         let uptime = read_uptime();
         let payload = HeartbeatPayload {
             hostname: self.hostname.clone(),
@@ -219,6 +228,7 @@ impl NatsPublisher {
             ip_address: get_primary_ip(),
             os: get_os_info(),
             arch: Some(std::env::consts::ARCH.to_string()),
+            channels,
         };
 
         match serde_json::to_vec(&payload) {
@@ -0,0 +1,277 @@
+#![cfg(feature = "prometheus")]
+
+use anyhow::{Context, Result};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use prometheus::{Encoder, GaugeVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::{debug, error, info};
+
+use crate::alerter::breaker::CircuitState;
+use crate::self_metrics::SelfMetrics;
+use crate::storage::Storage;
+use crate::types::{Label, MetricId, MetricSample};
+
+const NAMESPACE: &str = "sysops";
+
+/// Serves the latest value of every stored series on `/metrics`
+pub struct PrometheusExporter {
+    storage: Storage,
+    self_metrics: Arc<SelfMetrics>,
+    hostname: String,
+}
+
+impl PrometheusExporter {
+    pub fn new(storage: Storage, self_metrics: Arc<SelfMetrics>, hostname: String) -> Self {
+        Self { storage, self_metrics, hostname }
+    }
+
+    /// Bind the listener and serve scrapes in a background task
+    pub async fn spawn(self, bind: &str) -> Result<()> {
+        let listener = TcpListener::bind(bind)
+            .await
+            .with_context(|| format!("Failed to bind Prometheus endpoint: {}", bind))?;
+        info!(bind = %bind, "Prometheus endpoint listening");
+
+        let exporter = Arc::new(self);
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        error!(error = %e, "Failed to accept Prometheus connection");
+                        continue;
+                    }
+                };
+                let exporter = exporter.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| {
+                        let exporter = exporter.clone();
+                        async move { Ok::<_, Infallible>(exporter.handle(req)) }
+                    });
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        debug!(error = %e, peer = %peer, "Prometheus connection error");
+                    }
+                });
+            }
+        });
+
+        Ok(())
+    }
+
+    fn handle<B>(&self, req: Request<B>) -> Response<String> {
+        if req.method() != Method::GET || req.uri().path() != "/metrics" {
+            let mut resp = Response::new("Not Found\n".to_string());
+            *resp.status_mut() = StatusCode::NOT_FOUND;
+            return resp;
+        }
+
+        match self.render() {
+            Ok(body) => Response::builder()
+                .header("Content-Type", TextEncoder::new().format_type())
+                .body(body)
+                .unwrap_or_else(|_| Response::new(String::new())),
+            Err(e) => {
+                error!(error = %e, "Failed to render Prometheus metrics");
+                let mut resp = Response::new(format!("{}\n", e));
+                *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                resp
+            }
+        }
+    }
+
+    /// Build a fresh registry from the current storage snapshot and encode it
+    fn render(&self) -> Result<String> {
+        let registry = Registry::new();
+
+        let mut by_metric: BTreeMap<String, (MetricId, Vec<MetricSample>)> = BTreeMap::new();
+        for sample in self.storage.latest_all() {
+            by_metric.entry(sample.metric.to_string())
+                .or_insert_with(|| (sample.metric, Vec::new()))
+                .1
+                .push(sample);
+        }
+
+        for (metric, samples) in by_metric.values() {
+            let label_sets: Vec<&[Label]> = samples.iter().map(|s| s.labels.as_slice()).collect();
+            let (names, rows) = label_rows(&label_sets);
+            let gauge = GaugeVec::new(
+                Opts::new(metric_name(*metric), format!("Latest value of {}", metric)),
+                &names.iter().map(String::as_str).collect::<Vec<_>>(),
+            )?;
+            for (sample, values) in samples.iter().zip(&rows) {
+                let values: Vec<&str> = values.iter().map(String::as_str).collect();
+                gauge.with_label_values(&values).set(sample.value);
+            }
+            registry.register(Box::new(gauge))?;
+        }
+
+        self.register_self_metrics(&registry)?;
+        self.register_alerts(&registry)?;
+
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&registry.gather(), &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn register_self_metrics(&self, registry: &Registry) -> Result<()> {
+        let info = GaugeVec::new(
+            Opts::new(format!("{}_agent_info", NAMESPACE), "Agent build and host information"),
+            &["version", "hostname"],
+        )?;
+        info.with_label_values(&[env!("CARGO_PKG_VERSION"), &self.hostname]).set(1.0);
+        registry.register(Box::new(info))?;
+
+        let uptime = IntGauge::new(
+            format!("{}_agent_uptime_seconds", NAMESPACE),
+            "Seconds since the agent started",
+        )?;
+        uptime.set(self.self_metrics.uptime().as_secs() as i64);
+        registry.register(Box::new(uptime))?;
+
+        let series = IntGauge::new(
+            format!("{}_agent_series", NAMESPACE),
+            "Number of series held in memory",
+        )?;
+        series.set(self.storage.latest_all().len() as i64);
+        registry.register(Box::new(series))?;
+
+        let ingested = IntCounter::new(
+            format!("{}_agent_samples_ingested_total", NAMESPACE),
+            "Samples written to storage",
+        )?;
+        ingested.inc_by(self.self_metrics.samples_ingested());
+        registry.register(Box::new(ingested))?;
+
+        let collector_errors = IntCounterVec::new(
+            Opts::new(format!("{}_agent_collector_errors_total", NAMESPACE), "Failed collection runs"),
+            &["collector"],
+        )?;
+        for (collector, count) in self.self_metrics.collector_errors() {
+            collector_errors.with_label_values(&[&collector]).inc_by(count);
+        }
+        registry.register(Box::new(collector_errors))?;
+
+        let alerts = IntCounterVec::new(
+            Opts::new(format!("{}_agent_alerts_raised_total", NAMESPACE), "Alerts raised by analyzers"),
+            &["severity"],
+        )?;
+        for (severity, count) in self.self_metrics.alerts_raised() {
+            alerts.with_label_values(&[&severity.to_string().to_lowercase()]).inc_by(count);
+        }
+        registry.register(Box::new(alerts))?;
+
+        self.register_channel_health(registry)
+    }
+
+    /// Circuit breaker state and delivery counters per alert channel
+    fn register_channel_health(&self, registry: &Registry) -> Result<()> {
+        let state = IntGaugeVec::new(
+            Opts::new(format!("{}_agent_channel_state", NAMESPACE), "Circuit breaker state of an alert channel (1 for the current state)"),
+            &["channel", "state"],
+        )?;
+        let failures = IntCounterVec::new(
+            Opts::new(format!("{}_agent_channel_failures_total", NAMESPACE), "Failed alert channel sends"),
+            &["channel"],
+        )?;
+        let last_success = IntGaugeVec::new(
+            Opts::new(format!("{}_agent_channel_last_success_timestamp_seconds", NAMESPACE), "Time of the last successful send"),
+            &["channel"],
+        )?;
+        let dead_letters = IntGaugeVec::new(
+            Opts::new(format!("{}_agent_channel_dead_letters", NAMESPACE), "Notifications waiting in the dead-letter queue"),
+            &["channel"],
+        )?;
+        for health in self.self_metrics.channel_health() {
+            for s in CircuitState::ALL {
+                state.with_label_values(&[&health.channel, s.as_str()]).set((s == health.state) as i64);
+            }
+            failures.with_label_values(&[&health.channel]).inc_by(health.failures_total);
+            if let Some(t) = health.last_success {
+                last_success.with_label_values(&[&health.channel]).set(t.timestamp());
+            }
+            dead_letters.with_label_values(&[&health.channel]).set(health.dead_letters as i64);
+        }
+        registry.register(Box::new(state))?;
+        registry.register(Box::new(failures))?;
+        registry.register(Box::new(last_success))?;
+        registry.register(Box::new(dead_letters))?;
+
+        Ok(())
+    }
+
+    /// Active alerts in the same shape as Prometheus' own `ALERTS` series
+    fn register_alerts(&self, registry: &Registry) -> Result<()> {
+        let active = self.self_metrics.active_alerts();
+        let extra: Vec<[Label; 3]> = active.iter()
+            .map(|a| [
+                Label { key: "alertname".into(), value: a.metric.to_string() },
+                Label { key: "alertstate".into(), value: "firing".into() },
+                Label { key: "severity".into(), value: a.severity.to_string().to_lowercase() },
+            ])
+            .collect();
+        let merged: Vec<Vec<Label>> = active.iter().zip(&extra)
+            .map(|(a, e)| e.iter().chain(a.labels.iter()).cloned().collect())
+            .collect();
+        let label_sets: Vec<&[Label]> = merged.iter().map(Vec::as_slice).collect();
+        let (names, rows) = label_rows(&label_sets);
+
+        let gauge = GaugeVec::new(
+            Opts::new("ALERTS", "Alerts currently firing on this agent"),
+            &names.iter().map(String::as_str).collect::<Vec<_>>(),
+        )?;
+        for values in &rows {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            gauge.with_label_values(&values).set(1.0);
+        }
+        registry.register(Box::new(gauge))?;
+        Ok(())
+    }
+}
+
+/// `cpu.usage_percent` → `sysops_cpu_usage_percent`
+fn metric_name(metric: MetricId) -> String {
+    format!("{}_{}", NAMESPACE, sanitize(&metric.to_string()))
+}
+
+/// Replace characters that are not valid in Prometheus names with `_`
+fn sanitize(name: &str) -> String {
+    let mut out: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+/// Union of label names across all series of a family, and each series'
+/// values in that order (missing labels become empty, as Prometheus treats them)
+fn label_rows(label_sets: &[&[Label]]) -> (Vec<String>, Vec<Vec<String>>) {
+    let names: Vec<String> = label_sets.iter()
+        .flat_map(|labels| labels.iter().map(|l| sanitize(&l.key)))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let rows = label_sets.iter()
+        .map(|labels| {
+            names.iter()
+                .map(|name| {
+                    labels.iter()
+                        .find(|l| sanitize(&l.key) == *name)
+                        .map(|l| l.value.clone())
+                        .unwrap_or_default()
+                })
+                .collect()
+        })
+        .collect();
+    (names, rows)
+}
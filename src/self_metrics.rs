@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::alerter::delivery::{ChannelHealth, Outbox};
+use crate::storage::SeriesKey;
+use crate::types::{Alert, Severity};
+
+/// An alert counts as active while analyzers keep re-raising it within this window
+const ALERT_ACTIVE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counters describing the agent itself, shared between the pipeline tasks
+/// and the exporters (Prometheus endpoint, NATS heartbeat).
+pub struct SelfMetrics {
+    started: Instant,
+    samples_ingested: AtomicU64,
+    collector_errors: Mutex<HashMap<String, u64>>,
+    alerts_raised: Mutex<HashMap<Severity, u64>>,
+    active_alerts: Mutex<HashMap<SeriesKey, (Alert, Instant)>>,
+    channels: Mutex<Vec<Outbox>>,
+}
+
+impl SelfMetrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            samples_ingested: AtomicU64::new(0),
+            collector_errors: Mutex::new(HashMap::new()),
+            alerts_raised: Mutex::new(HashMap::new()),
+            active_alerts: Mutex::new(HashMap::new()),
+            channels: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn record_sample(&self) {
+        self.samples_ingested.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn samples_ingested(&self) -> u64 {
+        self.samples_ingested.load(Ordering::Relaxed)
+    }
+
+    pub fn record_collector_error(&self, collector: &str) {
+        *self.collector_errors.lock().unwrap()
+            .entry(collector.to_string())
+            .or_insert(0) += 1;
+    }
+
+    pub fn collector_errors(&self) -> Vec<(String, u64)> {
+        self.collector_errors.lock().unwrap()
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect()
+    }
+
+    /// Record an alert raised by an analyzer, before dedup/rate limiting
+    pub fn record_alert(&self, alert: &Alert) {
+        *self.alerts_raised.lock().unwrap().entry(alert.severity).or_insert(0) += 1;
+        let key = SeriesKey::new(alert.metric, &alert.labels);
+        self.active_alerts.lock().unwrap().insert(key, (alert.clone(), Instant::now()));
+    }
+
+    pub fn alerts_raised(&self) -> Vec<(Severity, u64)> {
+        self.alerts_raised.lock().unwrap()
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect()
+    }
+
+    /// Alerts re-raised within the last minute; older entries are dropped
+    pub fn active_alerts(&self) -> Vec<Alert> {
+        let mut active = self.active_alerts.lock().unwrap();
+        active.retain(|_, (_, seen)| seen.elapsed() < ALERT_ACTIVE_WINDOW);
+        active.values().map(|(alert, _)| alert.clone()).collect()
+    }
+
+    /// Report the health of these alert channels from now on
+    pub fn watch_channels(&self, outboxes: Vec<Outbox>) {
+        *self.channels.lock().unwrap() = outboxes;
+    }
+
+    pub fn channel_health(&self) -> Vec<ChannelHealth> {
+        self.channels.lock().unwrap().iter().map(Outbox::health).collect()
+    }
+}
+
+impl Default for SelfMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,282 @@
+#![cfg(feature = "sqlite")]
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+
+use crate::config::StorageConfig;
+use crate::storage::{SeriesKey, Storage};
+use crate::types::{Label, MetricId, MetricSample};
+
+/// Samples queued between the ingestion task and the writer thread
+const QUEUE_CAPACITY: usize = 20_000;
+/// Flush once this many samples are pending...
+const BATCH_SIZE: usize = 500;
+/// ...or when the oldest pending sample is this old
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// How often expired rows are deleted
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+/// How long shutdown waits for the final flush
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Only history this recent is loaded back into the ring buffers
+const WARM_UP_HOURS: i64 = 24;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS series (
+        id      INTEGER PRIMARY KEY,
+        metric  TEXT NOT NULL,
+        labels  TEXT NOT NULL,
+        UNIQUE (metric, labels)
+    );
+    CREATE TABLE IF NOT EXISTS samples (
+        series_id INTEGER NOT NULL REFERENCES series(id),
+        ts        INTEGER NOT NULL,
+        value     REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_samples_series_ts ON samples (series_id, ts);
+    CREATE INDEX IF NOT EXISTS idx_samples_ts ON samples (ts);
+";
+
+/// SQLite-backed metric history. Open it, warm up the in-memory storage,
+/// then hand the connection to a background writer with `spawn_writer`.
+pub struct SqliteStore {
+    conn: Connection,
+    path: PathBuf,
+    retention_days: u32,
+}
+
+/// Messages to the writer thread
+enum WriterMsg {
+    Sample(Box<MetricSample>),
+    /// Flush what is pending, then acknowledge and exit
+    Shutdown(Sender<()>),
+}
+
+/// Handle used by the ingestion task to queue samples for the writer thread
+#[derive(Clone)]
+pub struct SqliteWriter {
+    tx: SyncSender<WriterMsg>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `sqlite_path`, defaulting to `<data_dir>/metrics.db`
+    pub fn open(config: &StorageConfig, data_dir: &str) -> Result<Self> {
+        let path = match config.sqlite_path {
+            Some(ref p) => PathBuf::from(p),
+            None => PathBuf::from(data_dir).join("metrics.db"),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        let conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open SQLite database: {}", path.display()))?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        conn.execute_batch(SCHEMA)?;
+
+        info!(path = %path.display(), "SQLite store opened");
+
+        Ok(Self {
+            conn,
+            path,
+            retention_days: config.sqlite_retention_days,
+        })
+    }
+
+    /// Load up to `per_series` of the most recent samples of every series
+    /// into the ring buffers. Returns the number of samples loaded.
+    pub fn warm_up(&self, storage: &Storage, per_series: usize) -> Result<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::hours(WARM_UP_HOURS)).timestamp_millis();
+        let mut stmt = self.conn.prepare(
+            "SELECT s.metric, s.labels, r.ts, r.value FROM (
+                 SELECT series_id, ts, value,
+                        ROW_NUMBER() OVER (PARTITION BY series_id ORDER BY ts DESC) AS rn
+                 FROM samples WHERE ts >= ?1
+             ) r
+             JOIN series s ON s.id = r.series_id
+             WHERE r.rn <= ?2
+             ORDER BY r.series_id, r.ts",
+        )?;
+
+        let rows = stmt.query_map(params![cutoff, per_series as i64], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, f64>(3)?,
+            ))
+        })?;
+
+        let mut loaded = 0;
+        for row in rows {
+            let (metric, labels, ts, value) = row?;
+            // Rows for metrics this build no longer knows are skipped
+            let Ok(metric) = metric.parse::<MetricId>() else { continue };
+            let Ok(labels) = serde_json::from_str::<Vec<Label>>(&labels) else { continue };
+            let Some(timestamp) = DateTime::<Utc>::from_timestamp_millis(ts) else { continue };
+
+            storage.insert(MetricSample {
+                timestamp,
+                metric,
+                value,
+                labels: labels.into_iter().collect(),
+            });
+            loaded += 1;
+        }
+
+        info!(samples = loaded, "Warmed up ring buffers from SQLite");
+        Ok(loaded)
+    }
+
+    /// Move the connection onto a dedicated writer thread
+    pub fn spawn_writer(self) -> Result<SqliteWriter> {
+        let (tx, rx) = sync_channel(QUEUE_CAPACITY);
+        std::thread::Builder::new()
+            .name("sqlite-writer".into())
+            .spawn(move || self.writer_loop(rx))?;
+        Ok(SqliteWriter {
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    fn writer_loop(mut self, rx: Receiver<WriterMsg>) {
+        let mut batch: Vec<MetricSample> = Vec::with_capacity(BATCH_SIZE);
+        let mut series_ids: HashMap<SeriesKey, i64> = HashMap::new();
+        let mut last_flush = Instant::now();
+        let mut last_prune: Option<Instant> = None;
+
+        loop {
+            let timeout = FLUSH_INTERVAL.saturating_sub(last_flush.elapsed());
+            let mut ack = None;
+            let disconnected = match rx.recv_timeout(timeout) {
+                Ok(WriterMsg::Sample(sample)) => {
+                    batch.push(*sample);
+                    false
+                }
+                Ok(WriterMsg::Shutdown(done)) => {
+                    ack = Some(done);
+                    true
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if batch.len() >= BATCH_SIZE || last_flush.elapsed() >= FLUSH_INTERVAL || disconnected {
+                if !batch.is_empty() {
+                    if let Err(e) = self.flush(&batch, &mut series_ids) {
+                        error!(error = %e, count = batch.len(), "Failed to write samples to SQLite");
+                    }
+                    batch.clear();
+                }
+                last_flush = Instant::now();
+            }
+
+            if !disconnected && last_prune.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL) {
+                match self.prune() {
+                    Ok(n) if n > 0 => {
+                        // Emptied series rows may have been deleted; re-resolve ids
+                        series_ids.clear();
+                        info!(rows = n, "Pruned expired SQLite samples");
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!(error = %e, "SQLite retention pruning failed"),
+                }
+                last_prune = Some(Instant::now());
+            }
+
+            if disconnected {
+                debug!(path = %self.path.display(), "SQLite writer shutting down");
+                if let Some(done) = ack {
+                    let _ = done.send(());
+                }
+                return;
+            }
+        }
+    }
+
+    fn flush(
+        &mut self,
+        batch: &[MetricSample],
+        series_ids: &mut HashMap<SeriesKey, i64>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut upsert = tx.prepare_cached(
+                "INSERT INTO series (metric, labels) VALUES (?1, ?2)
+                 ON CONFLICT (metric, labels) DO UPDATE SET metric = excluded.metric
+                 RETURNING id",
+            )?;
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO samples (series_id, ts, value) VALUES (?1, ?2, ?3)",
+            )?;
+
+            for sample in batch {
+                let key = SeriesKey::new(sample.metric, &sample.labels);
+                let id = match series_ids.get(&key) {
+                    Some(&id) => id,
+                    None => {
+                        let labels = serde_json::to_string(&key.labels)?;
+                        let id: i64 = upsert.query_row(
+                            params![key.metric.to_string(), labels],
+                            |row| row.get(0),
+                        )?;
+                        series_ids.insert(key, id);
+                        id
+                    }
+                };
+                insert.execute(params![id, sample.timestamp.timestamp_millis(), sample.value])?;
+            }
+        }
+        tx.commit()?;
+        debug!(count = batch.len(), "Flushed samples to SQLite");
+        Ok(())
+    }
+
+    /// Delete samples past the retention period and series left without samples
+    fn prune(&mut self) -> Result<usize> {
+        let cutoff = (Utc::now() - chrono::Duration::days(self.retention_days as i64)).timestamp_millis();
+        let deleted = self.conn.execute("DELETE FROM samples WHERE ts < ?1", params![cutoff])?;
+        if deleted > 0 {
+            self.conn.execute(
+                "DELETE FROM series WHERE id NOT IN (SELECT DISTINCT series_id FROM samples)",
+                [],
+            )?;
+        }
+        Ok(deleted)
+    }
+}
+
+impl SqliteWriter {
+    /// Queue a sample without blocking; if the writer falls behind, samples
+    /// are dropped from persistence (they still reach the ring buffers).
+    pub fn write(&self, sample: &MetricSample) {
+        if self.tx.try_send(WriterMsg::Sample(Box::new(sample.clone()))).is_err() {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            if dropped % 1000 == 1 {
+                warn!(dropped, "SQLite write queue full, dropping samples");
+            }
+        }
+    }
+
+    /// Write out the samples still pending and stop the writer thread.
+    /// Blocks until the flush is done, so call it off the async runtime.
+    pub fn shutdown(&self) {
+        let (done_tx, done_rx) = channel();
+        if self.tx.send(WriterMsg::Shutdown(done_tx)).is_err() {
+            return;
+        }
+        if done_rx.recv_timeout(SHUTDOWN_TIMEOUT).is_err() {
+            warn!("Timed out flushing samples to SQLite on shutdown");
+        }
+    }
+}
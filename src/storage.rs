@@ -1,17 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 use crate::config::StorageConfig;
-use crate::types::{MetricId, MetricSample};
+use crate::types::{Label, MetricId, MetricSample};
 
-/// Thread-safe metric storage using ring buffers
+/// Identifies one time series: a metric plus its label set.
+/// Labels are kept sorted by key so the same set always hashes the same.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SeriesKey {
+    pub metric: MetricId,
+    pub labels: SmallVec<[Label; 4]>,
+}
+
+impl SeriesKey {
+    pub fn new(metric: MetricId, labels: &[Label]) -> Self {
+        let mut labels: SmallVec<[Label; 4]> = labels.iter().cloned().collect();
+        labels.sort();
+        Self { metric, labels }
+    }
+}
+
+impl std::fmt::Display for SeriesKey {
+    /// Prometheus-style rendering, e.g. `disk.usage_percent{mountpoint="/var"}`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.metric)?;
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self.labels.iter()
+                .map(|l| format!("{}=\"{}\"", l.key, l.value))
+                .collect();
+            write!(f, "{{{}}}", labels.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Thread-safe metric storage using one ring buffer per series
 #[derive(Clone)]
 pub struct Storage {
     inner: Arc<RwLock<StorageInner>>,
 }
 
 struct StorageInner {
-    buffers: HashMap<MetricId, RingBuffer>,
+    series: HashMap<MetricId, HashMap<SeriesKey, RingBuffer>>,
     capacity: usize,
 }
 
@@ -24,8 +57,9 @@ struct RingBuffer {
 
 impl RingBuffer {
     fn new(capacity: usize) -> Self {
+        // Grown on demand: most series (per process, per mount) never fill up
         Self {
-            data: Vec::with_capacity(capacity),
+            data: Vec::new(),
             head: 0,
             len: 0,
             capacity,
@@ -82,31 +116,128 @@ impl Storage {
     pub fn new(config: &StorageConfig) -> anyhow::Result<Self> {
         Ok(Self {
             inner: Arc::new(RwLock::new(StorageInner {
-                buffers: HashMap::new(),
+                series: HashMap::new(),
                 capacity: config.ring_buffer_size as usize,
             })),
         })
     }
 
     pub fn insert(&self, sample: MetricSample) {
+        let key = SeriesKey::new(sample.metric, &sample.labels);
         let mut inner = self.inner.write().unwrap();
         let capacity = inner.capacity;
-        let buffer = inner.buffers
+        let buffer = inner.series
             .entry(sample.metric)
+            .or_default()
+            .entry(key)
             .or_insert_with(|| RingBuffer::new(capacity));
         buffer.push(sample);
     }
 
-    pub fn latest(&self, metric: MetricId) -> Option<MetricSample> {
+    /// All series currently stored for a metric
+    pub fn series(&self, metric: MetricId) -> Vec<SeriesKey> {
         let inner = self.inner.read().unwrap();
-        inner.buffers.get(&metric)?.latest().cloned()
+        match inner.series.get(&metric) {
+            Some(by_labels) => by_labels.keys().cloned().collect(),
+            None => Vec::new(),
+        }
     }
 
-    pub fn recent(&self, metric: MetricId, n: usize) -> Vec<MetricSample> {
+    pub fn latest(&self, key: &SeriesKey) -> Option<MetricSample> {
         let inner = self.inner.read().unwrap();
-        match inner.buffers.get(&metric) {
+        inner.series.get(&key.metric)?.get(key)?.latest().cloned()
+    }
+
+    pub fn recent(&self, key: &SeriesKey, n: usize) -> Vec<MetricSample> {
+        let inner = self.inner.read().unwrap();
+        match inner.series.get(&key.metric).and_then(|m| m.get(key)) {
             Some(buf) => buf.recent(n).into_iter().cloned().collect(),
             None => Vec::new(),
         }
     }
+
+    /// Latest sample of every stored series
+    pub fn latest_all(&self) -> Vec<MetricSample> {
+        let inner = self.inner.read().unwrap();
+        inner.series.values()
+            .flat_map(|by_labels| by_labels.values())
+            .filter_map(|buf| buf.latest().cloned())
+            .collect()
+    }
+
+    /// Drop series whose latest sample is older than `cutoff`, e.g. exited
+    /// processes or unmounted filesystems. Returns the number removed.
+    pub fn prune_stale(&self, cutoff: DateTime<Utc>) -> usize {
+        let mut inner = self.inner.write().unwrap();
+        let mut removed = 0;
+        for by_labels in inner.series.values_mut() {
+            let before = by_labels.len();
+            by_labels.retain(|_, buf| buf.latest().is_some_and(|s| s.timestamp >= cutoff));
+            removed += before - by_labels.len();
+        }
+        inner.series.retain(|_, by_labels| !by_labels.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smallvec::smallvec;
+
+    fn label(key: &str, value: &str) -> Label {
+        Label { key: key.into(), value: value.into() }
+    }
+
+    fn sample(mountpoint: &str, value: f64, timestamp: DateTime<Utc>) -> MetricSample {
+        MetricSample {
+            timestamp,
+            metric: MetricId::DiskUsage,
+            value,
+            labels: smallvec![label("mountpoint", mountpoint)],
+        }
+    }
+
+    fn storage(ring_buffer_size: u32) -> Storage {
+        Storage::new(&StorageConfig { ring_buffer_size, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn series_key_ignores_label_order() {
+        let a = SeriesKey::new(MetricId::DiskUsage, &[label("mountpoint", "/var"), label("device", "sda1")]);
+        let b = SeriesKey::new(MetricId::DiskUsage, &[label("device", "sda1"), label("mountpoint", "/var")]);
+
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), r#"disk.usage_percent{device="sda1",mountpoint="/var"}"#);
+    }
+
+    #[test]
+    fn keeps_one_ring_buffer_per_series() {
+        let storage = storage(3);
+        let now = Utc::now();
+        for i in 0..5 {
+            storage.insert(sample("/", i as f64, now));
+        }
+        storage.insert(sample("/var", 42.0, now));
+
+        let root = SeriesKey::new(MetricId::DiskUsage, &[label("mountpoint", "/")]);
+        let values: Vec<f64> = storage.recent(&root, 10).iter().map(|s| s.value).collect();
+        assert_eq!(values, [2.0, 3.0, 4.0]);
+        assert_eq!(storage.series(MetricId::DiskUsage).len(), 2);
+    }
+
+    #[test]
+    fn prunes_series_without_recent_samples() {
+        let storage = storage(10);
+        let now = Utc::now();
+        storage.insert(sample("/", 1.0, now));
+        storage.insert(sample("/mnt/usb", 1.0, now - chrono::Duration::hours(2)));
+
+        assert_eq!(storage.prune_stale(now - chrono::Duration::hours(1)), 1);
+
+        let left: Vec<String> = storage.series(MetricId::DiskUsage).iter().map(|k| k.to_string()).collect();
+        assert_eq!(left, [r#"disk.usage_percent{mountpoint="/"}"#]);
+        assert_eq!(storage.prune_stale(now + chrono::Duration::seconds(1)), 1);
+        assert!(storage.latest_all().is_empty());
+    }
 }